@@ -0,0 +1,340 @@
+use std::{
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use dialoguer::Confirm;
+use edu_sync::{
+    account::Account,
+    config::{self, Config},
+};
+use tokio::{fs, task};
+
+use crate::util;
+
+/// Manages the configured accounts.
+#[derive(Debug, clap::Parser)]
+pub struct Subcommand {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    List(List),
+    Remove(Remove),
+    SetPath(SetPath),
+    SetLang(SetLang),
+    Check(Check),
+}
+
+impl Subcommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        match self.command {
+            Command::List(command) => command.run().await,
+            Command::Remove(command) => command.run().await,
+            Command::SetPath(command) => command.run().await,
+            Command::SetLang(command) => command.run().await,
+            Command::Check(command) => command.run().await,
+        }
+    }
+}
+
+/// Lists the configured accounts.
+#[derive(Debug, clap::Parser)]
+struct List {}
+
+impl List {
+    async fn run(self) -> anyhow::Result<()> {
+        let config = Config::read().await?;
+
+        if util::check_accounts(&config) {
+            for (name, account_config) in &config.accounts {
+                let courses = &account_config.courses.0;
+                let synced = courses.values().filter(|course| course.sync).count();
+                println!(
+                    "{name}: {} on {} at {} ({synced} of {} courses synced{})",
+                    account_config.user,
+                    account_config.site,
+                    account_config.path.display(),
+                    courses.len(),
+                    match &account_config.id.lang {
+                        Some(lang) => format!(", language {lang}"),
+                        None => String::new(),
+                    }
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes an account and its stored token.
+///
+/// Synced files are kept.
+#[derive(Debug, clap::Parser)]
+struct Remove {
+    /// The account as shown by `account list`.
+    account: String,
+    /// Bypass the confirmation.
+    #[clap(long)]
+    no_confirm: bool,
+}
+
+impl Remove {
+    async fn run(self) -> anyhow::Result<()> {
+        let (mut config, mut store) = util::read_config().await?;
+        let account_config = util::account_config(&mut config, &self.account)?;
+
+        if !self.no_confirm {
+            let prompt = format!("Remove {account_config}?");
+            let confirmed =
+                task::spawn_blocking(move || Confirm::new().with_prompt(prompt).interact())
+                    .await??;
+            if !confirmed {
+                return Ok(());
+            }
+        }
+
+        let account_config = config.accounts.remove(&self.account).unwrap();
+        store.remove(&account_config.token)?;
+        if let Some(private_token) = &account_config.private_token {
+            store.remove(private_token)?;
+        }
+        store.flush()?;
+        config.write().await?;
+
+        eprintln!("Removed {account_config}");
+        Ok(())
+    }
+}
+
+/// Changes the path an account downloads resources to.
+#[derive(Debug, clap::Parser)]
+struct SetPath {
+    /// The account as shown by `account list`.
+    account: String,
+    /// The new path to download resources to.
+    #[arg(value_hint = clap::ValueHint::DirPath)]
+    path: PathBuf,
+    /// Move the already synced files to the new path.
+    #[clap(long = "move")]
+    move_files: bool,
+}
+
+impl SetPath {
+    async fn run(self) -> anyhow::Result<()> {
+        let mut config = Config::read().await?;
+        let account_config = util::account_config(&mut config, &self.account)?;
+        let path = config::expand_path(&self.path)?;
+
+        if self.move_files && path != account_config.path {
+            if path.starts_with(&account_config.path) {
+                // Undo `expand_path` creating the directory, unless it is in use.
+                fs::remove_dir(&path).await.ok();
+                bail!(
+                    "{} is inside {}. Choose a path outside of the synced files.",
+                    path.display(),
+                    account_config.path.display()
+                );
+            }
+            // `expand_path` creates the directory if it does not exist.
+            if fs::read_dir(&path).await?.next_entry().await?.is_some() {
+                bail!(
+                    "{} is not empty. Empty it or move the files yourself.",
+                    path.display()
+                );
+            }
+            fs::remove_dir(&path).await?;
+            let res = match fs::rename(&account_config.path, &path).await {
+                Err(err) if err.kind() == ErrorKind::CrossesDevices => {
+                    move_dir(&account_config.path, &path).await
+                }
+                res => res,
+            };
+            if let Err(err) = res {
+                if !fs::try_exists(&path).await? {
+                    fs::create_dir(&path).await?;
+                }
+                if err.kind() == ErrorKind::NotFound {
+                    eprintln!(
+                        "{} does not exist. Nothing to move.",
+                        account_config.path.display()
+                    );
+                } else {
+                    return Err(err).with_context(|| {
+                        format!(
+                            "could not move {} to {}",
+                            account_config.path.display(),
+                            path.display()
+                        )
+                    });
+                }
+            }
+        }
+
+        account_config.path = path;
+        eprintln!("Set path of {account_config}");
+        config.write().await?;
+        Ok(())
+    }
+}
+
+/// Moves a directory to another file system by copying it and removing the
+/// original afterwards.
+///
+/// If copying fails, the partial copy is removed and the original is kept.
+async fn move_dir(from: &Path, to: &Path) -> io::Result<()> {
+    if let Err(err) = copy_dir(from, to).await {
+        if fs::try_exists(to).await? {
+            fs::remove_dir_all(to).await?;
+        }
+        return Err(err);
+    }
+    fs::remove_dir_all(from).await
+}
+
+async fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    let mut dirs = vec![(from.to_path_buf(), to.to_path_buf())];
+    while let Some((from, to)) = dirs.pop() {
+        fs::create_dir(&to).await?;
+        let mut entries = fs::read_dir(&from).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let target = to.join(entry.file_name());
+            let file_type = entry.file_type().await?;
+            if file_type.is_symlink() {
+                copy_symlink(&entry.path(), &target).await?;
+            } else if file_type.is_dir() {
+                dirs.push((entry.path(), target));
+            } else {
+                fs::copy(entry.path(), target).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Creates a symbolic link at `to` pointing where the one at `from` points.
+#[cfg(unix)]
+async fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    fs::symlink(fs::read_link(from).await?, to).await
+}
+
+/// Creates a symbolic link at `to` pointing where the one at `from` points.
+#[cfg(windows)]
+async fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    let link_target = fs::read_link(from).await?;
+    if fs::metadata(from)
+        .await
+        .is_ok_and(|metadata| metadata.is_dir())
+    {
+        fs::symlink_dir(link_target, to).await
+    } else {
+        fs::symlink_file(link_target, to).await
+    }
+}
+
+/// Changes the language to force for resource retrieval of an account.
+#[derive(Debug, clap::Parser)]
+struct SetLang {
+    /// The account as shown by `account list`.
+    account: String,
+    /// The language. If unset, the language of the user is used.
+    lang: Option<String>,
+}
+
+impl SetLang {
+    async fn run(self) -> anyhow::Result<()> {
+        let mut config = Config::read().await?;
+        let account_config = util::account_config(&mut config, &self.account)?;
+        account_config.id.lang = self.lang;
+        config.write().await?;
+        Ok(())
+    }
+}
+
+/// Checks whether the tokens of the accounts still work.
+#[derive(Debug, clap::Parser)]
+struct Check {
+    /// The account as shown by `account list`. If unset, all accounts are
+    /// checked.
+    account: Option<String>,
+}
+
+impl Check {
+    async fn run(self) -> anyhow::Result<()> {
+        let (mut config, mut store) = util::read_config().await?;
+        if let Some(account) = &self.account {
+            util::account_config(&mut config, account)?;
+        }
+
+        let mut success = true;
+        for (name, account_config) in &config.accounts {
+            if self.account.as_ref().is_some_and(|account| account != name) {
+                continue;
+            }
+            let token = store.get(&account_config.token)?;
+            let account = Account::new(account_config.id.clone(), token)
+                .with_http(account_config.http.client()?);
+            match account.get_info().await {
+                Ok(info) => println!(
+                    "{name}: OK, {} on {} (Moodle {})",
+                    info.full_name,
+                    info.site_name,
+                    info.release.as_deref().unwrap_or("release unknown")
+                ),
+                Err(err) => {
+                    success = false;
+                    println!("{name}: {err}");
+                    if let Some(hint) = err.ws_error().and_then(util::ws_error_hint) {
+                        println!("{hint}");
+                    }
+                }
+            }
+        }
+
+        if !success {
+            bail!("some accounts do not work");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn move_dir_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("from");
+        let to = dir.path().join("to");
+        std::fs::create_dir_all(from.join("course/section")).unwrap();
+        std::fs::write(from.join("course/section/notes.pdf"), "notes").unwrap();
+        std::os::unix::fs::symlink("course/section", from.join("latest")).unwrap();
+        std::os::unix::fs::symlink("course/section/notes.pdf", from.join("notes.pdf")).unwrap();
+
+        move_dir(&from, &to).await.unwrap();
+
+        assert!(!from.exists());
+        assert_eq!(
+            std::fs::read_to_string(to.join("course/section/notes.pdf")).unwrap(),
+            "notes"
+        );
+        for (link, target) in [
+            ("latest", "course/section"),
+            ("notes.pdf", "course/section/notes.pdf"),
+        ] {
+            let link = to.join(link);
+            assert!(link.symlink_metadata().unwrap().is_symlink());
+            assert_eq!(std::fs::read_link(&link).unwrap(), Path::new(target));
+        }
+        assert_eq!(
+            std::fs::read_to_string(to.join("latest/notes.pdf")).unwrap(),
+            "notes"
+        );
+    }
+}
@@ -1,35 +1,111 @@
-use std::path::PathBuf;
+use std::{
+    iter,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
 
-use dialoguer::Password;
+use anyhow::{bail, Context};
+use dialoguer::{Input, Password, Select};
 use edu_sync::{
-    account::Account,
-    config::{self, AccountConfig, Config},
+    account::{
+        self,
+        public_config::{self, LoginType},
+        qr, Account, Token,
+    },
+    config::{self, AccountConfig},
+    http::{self, HttpConfig},
+    secret::SecretRef,
+};
+use tokio::{
+    io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    sync::oneshot,
+    task, time,
 };
-use tokio::task;
 use url::Url;
 
+use crate::util;
+
+/// How long to wait for the browser to deliver the SSO token URL.
+const LISTEN_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
 /// Adds a new account to the configuration.
 #[derive(Debug, clap::Parser)]
+#[command(allow_missing_positional = true)]
 pub struct Subcommand {
     /// The username of the account.
     ///
     /// If set, you will be prompted the corresponding password which will be
-    /// used to retrieve the token. If unset, you must supply the token
-    /// yourself.
+    /// used to retrieve the token.
+    ///
+    /// If no login method is given, it is chosen based on the site's public
+    /// configuration: username and password if the site allows logging in via
+    /// the app, browser-based SSO otherwise.
+    #[structopt(short, long)]
+    username: Option<String>,
+    /// Log in via the browser using single sign-on.
+    ///
+    /// Use this if your Moodle instance logs you in via an external identity
+    /// provider such as Shibboleth or SAML. The login page is opened in your
+    /// browser. After logging in, Moodle redirects to a URL of the form
+    /// `moodlemobile://token=...`, which you have to paste into the prompt.
+    /// Most browsers show this URL in the developer tools' network tab if no
+    /// application handles it.
+    #[arg(long, conflicts_with_all = ["username", "qr", "token"])]
+    sso: bool,
+    /// Receive the SSO token URL via a short-lived local HTTP listener.
+    ///
+    /// Implies `--sso`.
+    /// Moodle then redirects to a local address instead of
+    /// `moodlemobile://`, so the token URL does not have to be pasted.
+    /// Pasting still works while waiting. Some Moodle instances only accept
+    /// the official URL scheme; if the redirect fails, retry without this
+    /// option.
+    #[arg(long, conflicts_with_all = ["username", "qr", "token"])]
+    listen: bool,
+    /// Log in via the QR code for the Moodle app.
+    ///
+    /// Takes either the content of the QR code or the path to an image of it.
+    /// Find the QR code on your Moodle instance's website in your user
+    /// preferences under "Moodle app". The QR code is only valid for a few
+    /// minutes and only from the same network. The site URL is taken from the
+    /// QR code and may be omitted.
+    #[arg(
+        long,
+        value_name = "PAYLOAD_OR_IMAGE",
+        conflicts_with_all = ["username", "token"]
+    )]
+    qr: Option<String>,
+    /// Enter a token for the Moodle mobile web service yourself.
     ///
     /// Find the token on your Moodle instance's website in your user account
     /// settings as the security key for the Moodle mobile web service. If you
     /// cannot find it, make sure that your Moodle instance supports the Moodle
     /// mobile web service, which is also required for the official Moodle app.
     /// The token is being saved your config file.
-    #[structopt(short, long)]
-    username: Option<String>,
+    #[arg(long, conflicts_with = "username")]
+    token: bool,
     /// A language to force for resource retrieval.
     #[structopt(short, long)]
     lang: Option<String>,
+    /// A proxy for connecting to the site, such as
+    /// `http://proxy.example.com:3128` or `socks5h://localhost:1080`.
+    ///
+    /// Timeouts and the user agent can be set in the `http` table of the
+    /// account in the configuration file.
+    #[arg(long, value_name = "URL")]
+    proxy: Option<Url>,
+    /// A PEM file with root certificates to trust, such as the certificate
+    /// authority of your university.
+    ///
+    /// May be given multiple times.
+    #[arg(long, value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+    root_certificate: Vec<PathBuf>,
     /// The URL of the Moodle instance.
-    #[arg(value_hint = clap::ValueHint::Hostname)]
-    url: Url,
+    #[arg(value_hint = clap::ValueHint::Hostname, required_unless_present = "qr")]
+    url: Option<Url>,
     /// The path to download resources to.
     #[arg(value_hint = clap::ValueHint::DirPath)]
     path: PathBuf,
@@ -37,30 +113,321 @@ pub struct Subcommand {
 
 impl Subcommand {
     pub async fn run(self) -> anyhow::Result<()> {
-        let config_task = tokio::spawn(Config::read());
+        let qr_payload = match self.qr {
+            Some(qr) => Some(qr_payload(qr).await?),
+            None => None,
+        };
+        let url = match &qr_payload {
+            Some(payload) => payload.site_url.clone(),
+            None => self.url.unwrap(),
+        };
+        let http_config = HttpConfig {
+            root_certificates: self.root_certificate,
+            proxy: self.proxy,
+            ..HttpConfig::default()
+        };
+        let http = http_config.client()?;
 
-        let token = if let Some(username) = self.username {
-            let password =
-                task::spawn_blocking(|| Password::new().with_prompt("Password").interact())
-                    .await??;
-            Account::login(&self.url, &username, &password).await?.token
+        let public_config = match Account::public_config(http.clone(), &url).await {
+            Ok(public_config) => {
+                check_public_config(&public_config)?;
+                Some(public_config)
+            }
+            Err(err) => {
+                eprintln!("Could not retrieve the public site configuration: {err}");
+                None
+            }
+        };
+
+        let method = if let Some(payload) = qr_payload {
+            Method::Qr(payload)
+        } else if let Some(username) = self.username {
+            Method::Password(Some(username))
+        } else if self.sso || self.listen {
+            Method::Sso(None)
+        } else if self.token {
+            Method::Token
+        } else if let Some(public_config) = &public_config {
+            choose_method(public_config).await?
         } else {
-            task::spawn_blocking(|| Password::new().with_prompt("Token").interact())
-                .await??
-                .parse()?
+            Method::Token
         };
 
+        let (token, private_token) = log_in(
+            method,
+            http.clone(),
+            &url,
+            public_config,
+            self.listen,
+            self.lang.clone(),
+        )
+        .await?;
+
         let expanded_path = config::expand_path(&self.path)?;
-        let account_config = AccountConfig::new(self.url, token, expanded_path, self.lang).await?;
-        let mut config = config_task.await??;
+        let mut account_config =
+            AccountConfig::new(http, url, token, expanded_path, self.lang).await?;
+        account_config.private_token = private_token.map(SecretRef::Plaintext);
+        account_config.http = http_config;
+        let (mut config, mut store) = util::read_config().await?;
         let account_name = account_config.to_string();
-        config
+        let old_account_config = config
             .accounts
             .insert(account_config.id.to_string(), account_config);
+        if let Some(old_account_config) = old_account_config {
+            store.remove(&old_account_config.token)?;
+            if let Some(private_token) = &old_account_config.private_token {
+                store.remove(private_token)?;
+            }
+        }
+        let old_secret_refs = config.store_tokens(&mut store)?;
         config.write().await?;
+        store.remove_all(&old_secret_refs)?;
 
         eprintln!("Successfully added {}", account_name);
 
         Ok(())
     }
 }
+
+/// Logs in with `method` and returns the token and, if the site sent one, the
+/// private token.
+pub async fn log_in(
+    method: Method,
+    http: http::Client,
+    url: &Url,
+    public_config: Option<public_config::Config>,
+    listen: bool,
+    lang: Option<String>,
+) -> anyhow::Result<(Token, Option<String>)> {
+    let tokens = match method {
+        Method::Qr(payload) => {
+            let response = Account::qr_login(http, &payload).await?;
+            (response.token, response.private_token)
+        }
+        Method::Password(username) => {
+            let username = match username {
+                Some(username) => username,
+                None => {
+                    task::spawn_blocking(|| {
+                        Input::<String>::new()
+                            .with_prompt("Username")
+                            .interact_text()
+                    })
+                    .await??
+                }
+            };
+            let password =
+                task::spawn_blocking(|| Password::new().with_prompt("Password").interact())
+                    .await??;
+            let response = Account::login(http, url, &username, &password).await?;
+            (response.token, response.private_token)
+        }
+        Method::Sso(identity_provider) => {
+            let mut launch_url = public_config
+                .and_then(|public_config| public_config.launch_url)
+                .context("the site does not support SSO for the Moodle app")?;
+            if let Some(id) = identity_provider {
+                launch_url
+                    .query_pairs_mut()
+                    .append_pair("oauthsso", &id.to_string());
+            }
+            let token = sso_token(http, url, launch_url, listen, lang).await?;
+            (token, None)
+        }
+        Method::Token => {
+            let token = task::spawn_blocking(|| Password::new().with_prompt("Token").interact())
+                .await??
+                .parse()?;
+            (token, None)
+        }
+    };
+    Ok(tokens)
+}
+
+/// A way to retrieve a token.
+pub enum Method {
+    Qr(qr::Payload),
+    Password(Option<String>),
+    /// Browser-based SSO, optionally via an OAuth 2 identity provider.
+    Sso(Option<u64>),
+    Token,
+}
+
+/// Fails if the site cannot be used with the Moodle app currently.
+fn check_public_config(public_config: &public_config::Config) -> anyhow::Result<()> {
+    if !public_config.web_services || !public_config.mobile_service {
+        bail!(
+            "{} does not allow access via the Moodle app. Edu Sync requires the Moodle mobile web \
+             service. Ask your site administrator to enable it.",
+            public_config.site_name
+        );
+    }
+    if public_config.maintenance {
+        let message = public_config.maintenance_message.trim();
+        bail!(
+            "{} is in maintenance mode. Please try again later.{}{message}",
+            public_config.site_name,
+            if message.is_empty() { "" } else { "\n" },
+        );
+    }
+    Ok(())
+}
+
+/// Chooses the login method based on the public configuration of the site.
+pub async fn choose_method(public_config: &public_config::Config) -> anyhow::Result<Method> {
+    let identity_providers = public_config
+        .identity_providers
+        .iter()
+        .flatten()
+        .filter_map(|identity_provider| {
+            identity_provider
+                .id()
+                .map(|id| (identity_provider.name.clone(), id))
+        })
+        .collect::<Vec<_>>();
+
+    match public_config.login_type {
+        LoginType::Browser | LoginType::Embedded => {
+            eprintln!(
+                "{} requires logging in via the browser.",
+                public_config.site_name
+            );
+            return Ok(Method::Sso(None));
+        }
+        LoginType::App if identity_providers.is_empty() => return Ok(Method::Password(None)),
+        LoginType::App => {}
+    }
+
+    eprintln!(
+        "{} offers logging in via {}.",
+        public_config.site_name,
+        identity_providers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let items = iter::once("Username and password".to_string())
+        .chain(identity_providers.iter().map(|(name, _)| name.clone()))
+        .collect::<Vec<_>>();
+    let selection = task::spawn_blocking(move || {
+        Select::new()
+            .with_prompt("Log in with")
+            .items(&items)
+            .default(0)
+            .interact()
+    })
+    .await??;
+    let method = match selection.checked_sub(1) {
+        None => Method::Password(None),
+        Some(index) => Method::Sso(Some(identity_providers[index].1)),
+    };
+    Ok(method)
+}
+
+/// Reads the QR login payload from an image file or the argument itself.
+async fn qr_payload(qr: String) -> anyhow::Result<qr::Payload> {
+    let content = if Path::new(&qr).is_file() {
+        task::spawn_blocking(move || decode_qr_image(Path::new(&qr))).await??
+    } else {
+        qr
+    };
+    Ok(content.parse()?)
+}
+
+fn decode_qr_image(path: &Path) -> anyhow::Result<String> {
+    let image = image::open(path)
+        .with_context(|| format!("could not read {}", path.display()))?
+        .to_luma8();
+    let mut image = rqrr::PreparedImage::prepare(image);
+    let grid = image
+        .detect_grids()
+        .into_iter()
+        .next()
+        .context("no QR code found in the image")?;
+    let (_, content) = grid.decode()?;
+    Ok(content)
+}
+
+/// Retrieves a token via the SSO flow of the Moodle app.
+async fn sso_token(
+    http: http::Client,
+    site_url: &Url,
+    launch_url: Url,
+    listen: bool,
+    lang: Option<String>,
+) -> anyhow::Result<Token> {
+    let listener = if listen {
+        Some(TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?)
+    } else {
+        None
+    };
+    let url_scheme = match &listener {
+        // Moodle appends `://token=...` to the scheme.
+        Some(listener) => format!("http://{}/", listener.local_addr()?),
+        None => "moodlemobile".to_string(),
+    };
+    let (launch_url, builder) =
+        account::Builder::with_launch_url(http, site_url.clone(), launch_url, &url_scheme, lang);
+
+    eprintln!("Log in via your browser at\n{launch_url}");
+    if open::that_detached(launch_url.as_str()).is_err() {
+        eprintln!("Could not open a browser. Please open the URL manually.");
+    }
+
+    // Not a blocking task, as the runtime would wait for it on shutdown if the
+    // listener wins.
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        let input = Input::<String>::new()
+            .with_prompt("Token URL (moodlemobile://token=...)")
+            .interact_text();
+        sender.send(input).ok();
+    });
+    let prompt = async {
+        let input = receiver.await??;
+        let token_url = input.trim().parse::<Url>().context("invalid token URL")?;
+        anyhow::Ok(token_url)
+    };
+
+    let token_url = match listener {
+        Some(listener) => tokio::select! {
+            token_url = prompt => token_url?,
+            Ok(token_url) = time::timeout(LISTEN_TIMEOUT, receive_token_url(&listener)) => {
+                eprintln!();
+                token_url?
+            }
+        },
+        None => prompt.await?,
+    };
+
+    let account = builder.validate(&token_url).await?;
+    Ok(account.token())
+}
+
+/// Accepts connections until a request carries a token URL.
+async fn receive_token_url(listener: &TcpListener) -> io::Result<Url> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let mut request_line = String::new();
+        BufReader::new(&mut stream)
+            .read_line(&mut request_line)
+            .await?;
+        let token_url = request_line
+            .split(' ')
+            .nth(1)
+            .and_then(|target| target.find("token=").map(|index| &target[index..]))
+            .and_then(|token| Url::parse(&format!("moodlemobile://{token}")).ok());
+
+        let response: &[u8] = if token_url.is_some() {
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nConnection: close\r\n\r\nLogged in. You can close this window now.\n"
+        } else {
+            b"HTTP/1.1 404 Not Found\r\nConnection: close\r\n\r\n"
+        };
+        stream.write_all(response).await?;
+
+        if let Some(token_url) = token_url {
+            return Ok(token_url);
+        }
+    }
+}
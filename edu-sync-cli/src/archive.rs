@@ -0,0 +1,103 @@
+use std::{fs, sync::Arc};
+
+use anyhow::bail;
+use edu_sync::{
+    account::Account,
+    archive::{self, Format, Manifest},
+    config::CourseSelector,
+};
+use tokio::task;
+
+use crate::{
+    sync::{SyncCourse, Syncer},
+    util,
+};
+
+/// Archives a finished course.
+///
+/// Runs a final sync of the course that also captures its forums and grades,
+/// writes a manifest of all files to the course directory and marks the
+/// course as archived, so that it is no longer synced.
+#[derive(Debug, clap::Parser)]
+pub struct Subcommand {
+    /// The course id or case-insensitive name, which may contain wildcards.
+    course: CourseSelector,
+    /// Only consider the courses of this account, as shown by `account list`.
+    #[arg(short, long)]
+    account: Option<String>,
+    /// Pack the course directory into a file next to it, either `tar.gz` or
+    /// `zip`.
+    #[arg(long, value_name = "FORMAT")]
+    pack: Option<Format>,
+    /// Bypass any and all “Are you sure?” messages. It’s not a good idea to do
+    /// this unless you want to run edu-sync-cli from a script.
+    #[arg(long)]
+    no_confirm: bool,
+}
+
+impl Subcommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let (mut config, mut store) = util::read_config().await?;
+        if !util::check_accounts(&config) {
+            return Ok(());
+        }
+        let (name, id) = util::unique_course(&mut config, self.account.as_deref(), &self.course)?;
+
+        let account_config = &config.accounts[&name];
+        let course_config = &account_config.courses.0[&id];
+        let course_name = course_config.name.clone();
+        let course_path = account_config
+            .path
+            .join(course_config.name_as_path_component().as_ref());
+        let token = store.get(&account_config.token)?;
+        let account = Account::new(account_config.id.clone(), token)
+            .with_http(account_config.http.client()?)
+            .with_capabilities(account_config.capabilities.clone())
+            .with_text_format(account_config.text_format)
+            .with_url_format(account_config.url_format)
+            .with_link_index(account_config.link_index);
+        let course = SyncCourse {
+            account: Arc::new(account),
+            id,
+            name: course_name.clone(),
+            path: course_path.clone(),
+            archive: true,
+        };
+        let account_id = account_config.id.clone();
+        let mut syncer = Syncer::new(config.parallel_downloads, vec![course]).await;
+        syncer
+            .renew_tokens(&mut config, &mut store, self.no_confirm)
+            .await;
+        if !syncer.sync(self.no_confirm).await? {
+            bail!("{course_name} was not archived, as the final sync did not complete.");
+        }
+
+        let manifest_path = course_path.clone();
+        task::spawn_blocking(move || {
+            fs::create_dir_all(&manifest_path)?;
+            Manifest::new(id, course_name, &account_id, &manifest_path)?.write(&manifest_path)
+        })
+        .await??;
+
+        let course_config = config
+            .accounts
+            .get_mut(&name)
+            .and_then(|account_config| account_config.courses.0.get_mut(&id))
+            .unwrap();
+        course_config.archived = true;
+        eprintln!(
+            "Archived {} to {}",
+            course_config.name,
+            course_path.display()
+        );
+        config.write().await?;
+
+        if let Some(format) = self.pack {
+            let packed_path =
+                task::spawn_blocking(move || archive::pack(&course_path, format)).await??;
+            println!("{}", packed_path.display());
+        }
+
+        Ok(())
+    }
+}
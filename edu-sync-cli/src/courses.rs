@@ -0,0 +1,271 @@
+use std::{cmp::Reverse, collections::BTreeMap};
+
+use anyhow::{bail, Context};
+use dialoguer::MultiSelect;
+use edu_sync::{
+    account::Account,
+    config::{Config, CourseSelector},
+};
+use time::{macros::format_description, OffsetDateTime};
+use tokio::task;
+
+use crate::util;
+
+/// Selects the courses to synchronize.
+#[derive(Debug, clap::Parser)]
+pub struct Subcommand {
+    /// Only consider the courses of this account, as shown by `account list`.
+    #[arg(short, long, global = true)]
+    account: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    List(List),
+    /// Enables the synchronization of courses.
+    Enable(Select),
+    /// Disables the synchronization of courses.
+    Disable(Select),
+    Pick(Pick),
+    SetAlias(SetAlias),
+}
+
+impl Subcommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let account = self.account.as_deref();
+        match self.command {
+            Command::List(command) => command.run(account).await,
+            Command::Enable(command) => command.run(account, true).await,
+            Command::Disable(command) => command.run(account, false).await,
+            Command::Pick(command) => command.run(account).await,
+            Command::SetAlias(command) => command.run(account).await,
+        }
+    }
+}
+
+/// Lists the known courses and whether they are synchronized.
+#[derive(Debug, clap::Parser)]
+struct List {}
+
+impl List {
+    async fn run(self, account: Option<&str>) -> anyhow::Result<()> {
+        let mut config = Config::read().await?;
+
+        if util::check_accounts(&config) {
+            for (name, account_config) in util::accounts(&mut config, account)? {
+                println!("{name}:");
+                for course_config in account_config.courses.0.values().rev() {
+                    let mark = if course_config.sync { 'x' } else { ' ' };
+                    let alias = match &course_config.alias {
+                        Some(alias) => format!(" (alias {alias})"),
+                        None => String::new(),
+                    };
+                    let archived = if course_config.archived {
+                        " (archived)"
+                    } else {
+                        ""
+                    };
+                    println!("  [{mark}] {}{alias}{archived}", course_config.name);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, clap::Parser)]
+struct Select {
+    /// Course ids or case-insensitive names, which may contain wildcards such
+    /// as `*algebra*`.
+    #[arg(required = true)]
+    courses: Vec<CourseSelector>,
+}
+
+impl Select {
+    async fn run(self, account: Option<&str>, sync: bool) -> anyhow::Result<()> {
+        let mut config = Config::read().await?;
+        if !util::check_accounts(&config) {
+            return Ok(());
+        }
+
+        let mut matched = vec![false; self.courses.len()];
+        for (_, account_config) in util::accounts(&mut config, account)? {
+            for (&id, course_config) in &mut account_config.courses.0 {
+                let mut selected = false;
+                for (selector, matched) in self.courses.iter().zip(&mut matched) {
+                    if selector.matches(id, course_config) {
+                        *matched = true;
+                        selected = true;
+                    }
+                }
+                if selected && course_config.sync != sync {
+                    course_config.sync = sync;
+                    let action = if sync { "Enabled" } else { "Disabled" };
+                    eprintln!("{action} {}", course_config.name);
+                }
+            }
+        }
+        for (selector, matched) in self.courses.iter().zip(matched) {
+            if !matched {
+                eprintln!("No course matches {selector}. To update the courses, use fetch.");
+            }
+        }
+
+        config.write().await?;
+        Ok(())
+    }
+}
+
+/// Fetches the available courses and picks the ones to synchronize
+/// interactively.
+#[derive(Debug, clap::Parser)]
+struct Pick {}
+
+impl Pick {
+    async fn run(self, account: Option<&str>) -> anyhow::Result<()> {
+        let (mut config, mut store) = util::read_config().await?;
+        if !util::check_accounts(&config) {
+            return Ok(());
+        }
+
+        let mut items = Vec::new();
+        let mut selection = Vec::new();
+        let mut defaults = Vec::new();
+        let names = util::accounts(&mut config, account)?
+            .into_iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        let multiple_accounts = names.len() > 1;
+        for name in names {
+            let account_config = &config.accounts[&name];
+            let token = store.get(&account_config.token)?;
+            let mut account = Account::new(account_config.id.clone(), token)
+                .with_http(account_config.http.client()?)
+                .with_capabilities(account_config.capabilities.clone());
+            let mut account_courses = account.get_courses().await;
+            if matches!(&account_courses, Err(err) if err.is_invalid_token()) {
+                let Some(token) = util::renew_token(&mut config, &mut store, &name, false).await
+                else {
+                    continue;
+                };
+                account = account.with_token(token);
+                account_courses = account.get_courses().await;
+            }
+            let mut account_courses =
+                account_courses.with_context(|| format!("could not get the courses of {name}"))?;
+            let account_config = config.accounts.get_mut(&name).unwrap();
+            let categories = if account_config.rules.needs_categories() {
+                account
+                    .get_category_paths()
+                    .await
+                    .with_context(|| format!("could not get the categories of {name}"))?
+            } else {
+                BTreeMap::new()
+            };
+            account_courses.sort_unstable_by_key(|course| Reverse(course.id));
+            for course in &account_courses {
+                let mut details = vec![format!(
+                    "{} – {}",
+                    format_date(course.start_date),
+                    format_date(course.end_date)
+                )];
+                if !course.visible {
+                    details.push("hidden".to_string());
+                }
+                if multiple_accounts {
+                    details.push(name.clone());
+                }
+                items.push(format!("{} ({})", course.full_name, details.join(", ")));
+                selection.push((name.clone(), course.id));
+            }
+            let ids = account_courses
+                .iter()
+                .map(|course| course.id)
+                .collect::<Vec<_>>();
+            account_config
+                .courses
+                .update(account_courses, &account_config.rules, &categories);
+            defaults.extend(ids.into_iter().map(|id| account_config.courses.0[&id].sync));
+        }
+
+        let picked = task::spawn_blocking(move || {
+            MultiSelect::new()
+                .with_prompt("Courses to synchronize (space to toggle, enter to confirm)")
+                .items(&items)
+                .defaults(&defaults)
+                .interact_opt()
+        })
+        .await??;
+        let Some(picked) = picked else {
+            return Ok(());
+        };
+
+        for (index, (name, id)) in selection.into_iter().enumerate() {
+            let course_config = config
+                .accounts
+                .get_mut(&name)
+                .and_then(|account_config| account_config.courses.0.get_mut(&id))
+                .unwrap();
+            course_config.sync = picked.contains(&index);
+        }
+
+        config.write().await?;
+        Ok(())
+    }
+}
+
+/// Sets the alias of a course, which is used as its directory name.
+///
+/// Unlike the name, the alias stays the same if the course is renamed. Already
+/// synced files are not moved.
+#[derive(Debug, clap::Parser)]
+struct SetAlias {
+    /// The course id or case-insensitive name, which may contain wildcards.
+    course: CourseSelector,
+    /// The alias. If unset, the name of the course is used.
+    alias: Option<String>,
+}
+
+impl SetAlias {
+    async fn run(self, account: Option<&str>) -> anyhow::Result<()> {
+        let mut config = Config::read().await?;
+        let (name, id) = util::unique_course(&mut config, account, &self.course)?;
+        let course_configs = &mut config.accounts.get_mut(&name).unwrap().courses;
+        if let Some(alias) = &self.alias {
+            if alias.trim().is_empty() {
+                bail!("The alias must not be empty. Omit it to use the name of the course.");
+            }
+            if let Some((other_id, other)) = course_configs.alias_conflict(id, alias) {
+                bail!(
+                    "\"{alias}\" would be confused with \"{}\" ({other_id}), whose directory is \
+                     {}. Choose another alias.",
+                    other.name,
+                    other.name_as_path_component()
+                );
+            }
+        }
+        let course_config = course_configs.0.get_mut(&id).unwrap();
+        course_config.alias = self.alias;
+        eprintln!(
+            "The directory of {} is now {}",
+            course_config.name,
+            course_config.name_as_path_component()
+        );
+
+        config.write().await?;
+        Ok(())
+    }
+}
+
+fn format_date(date: Option<OffsetDateTime>) -> String {
+    // Moodle uses 0 for unset dates.
+    date.filter(|date| date.unix_timestamp() > 0)
+        .and_then(|date| {
+            date.format(format_description!("[year]-[month]-[day]"))
+                .ok()
+        })
+        .unwrap_or_else(|| "?".to_string())
+}
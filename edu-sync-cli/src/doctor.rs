@@ -0,0 +1,294 @@
+use std::{fs, io, path::PathBuf};
+
+use anyhow::bail;
+use edu_sync::{
+    account::Account,
+    config::AccountConfig,
+    http::{self, HttpConfig},
+    secret::Store,
+};
+use tokio::task;
+use url::Url;
+
+use crate::util;
+
+/// The functions required for syncing.
+const REQUIRED_FUNCTIONS: [&str; 3] = [
+    "core_webservice_get_site_info",
+    "core_enrol_get_users_courses",
+    "core_course_get_contents",
+];
+
+/// The file created to check that the download directory is writable.
+const PROBE_FILE_NAME: &str = ".edu-sync-doctor.tmp";
+
+/// Diagnoses problems with a site or an account.
+///
+/// Checks the public configuration of the site, the token, sample requests
+/// and access to the download directory and prints how to resolve any
+/// failures.
+#[derive(Debug, clap::Parser)]
+pub struct Subcommand {
+    /// The account as shown by `account list` or the URL of a site, such as
+    /// `https://moodle.example.com`. If unset, all accounts are checked.
+    target: Option<String>,
+}
+
+impl Subcommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let mut checklist = Checklist::default();
+
+        if let Some(site_url) = self.target.as_deref().and_then(site_url) {
+            println!("{site_url}:");
+            let http = HttpConfig::default().client()?;
+            checklist.check_site(http, &site_url).await;
+        } else {
+            let (mut config, mut store) = util::read_config().await?;
+            if !util::check_accounts(&config) {
+                return Ok(());
+            }
+            for (name, account_config) in util::accounts(&mut config, self.target.as_deref())? {
+                println!("{name}:");
+                checklist.check_account(account_config, &mut store).await?;
+            }
+        }
+
+        if checklist.failed {
+            bail!("some checks failed");
+        }
+        Ok(())
+    }
+}
+
+/// Parses `target` as a site URL, if it is one.
+fn site_url(target: &str) -> Option<Url> {
+    let url = Url::parse(target).ok()?;
+    matches!(url.scheme(), "http" | "https").then_some(url)
+}
+
+#[derive(Debug, Default)]
+struct Checklist {
+    failed: bool,
+}
+
+impl Checklist {
+    fn pass(&self, check: &str) {
+        println!("  [ok]   {check}");
+    }
+
+    fn fail(&mut self, check: &str, hint: &str) {
+        println!("  [fail] {check}");
+        println!("         {hint}");
+        self.failed = true;
+    }
+
+    fn skip(&self, check: &str) {
+        println!("  [skip] {check}");
+    }
+
+    fn check(&mut self, check: &str, ok: bool, hint: &str) -> bool {
+        if ok {
+            self.pass(check);
+        } else {
+            self.fail(check, hint);
+        }
+        ok
+    }
+
+    /// Checks the public configuration of the site.
+    ///
+    /// Returns whether the web service can be used.
+    async fn check_site(&mut self, http: http::Client, site_url: &Url) -> bool {
+        let public_config = match Account::public_config(http, site_url).await {
+            Ok(public_config) => public_config,
+            Err(err) => {
+                self.fail(
+                    &format!("Site reachable: {err}"),
+                    "Check the URL and your internet connection. The site may not run Moodle or \
+                     may block the mobile app.",
+                );
+                return false;
+            }
+        };
+        self.pass(&format!("Site reachable: {}", public_config.site_name));
+
+        let maintenance_hint = if public_config.maintenance_message.is_empty() {
+            "The site is in maintenance mode. Try again later.".to_string()
+        } else {
+            format!(
+                "The site is in maintenance mode: {}",
+                public_config.maintenance_message.trim()
+            )
+        };
+        let not_in_maintenance = self.check(
+            "Site not in maintenance mode",
+            !public_config.maintenance,
+            &maintenance_hint,
+        );
+        let web_services = self.check(
+            "Web services enabled",
+            public_config.web_services,
+            "Ask the site administrators to enable web services.",
+        );
+        let mobile_service = self.check(
+            "Mobile app service enabled",
+            public_config.mobile_service,
+            "Ask the site administrators to enable web services for mobile devices.",
+        );
+        not_in_maintenance && web_services && mobile_service
+    }
+
+    async fn check_account(
+        &mut self,
+        account_config: &AccountConfig,
+        store: &mut Store,
+    ) -> anyhow::Result<()> {
+        let http = match account_config.http.client() {
+            Ok(http) => {
+                if !account_config.http.is_default() {
+                    self.pass("HTTP settings valid");
+                }
+                Some(http)
+            }
+            Err(err) => {
+                self.fail(
+                    &format!("HTTP settings valid: {err}"),
+                    "Check the `http` table of the account in the configuration file.",
+                );
+                None
+            }
+        };
+        match http {
+            Some(http)
+                if self
+                    .check_site(http.clone(), &account_config.id.site_url)
+                    .await =>
+            {
+                let token = store.get(&account_config.token)?;
+                let account = Account::new(account_config.id.clone(), token)
+                    .with_http(http)
+                    .with_link_index(false);
+                self.check_web_service(&account, account_config).await;
+            }
+            Some(_) => self.skip("Token valid"),
+            None => {
+                self.skip("Site reachable");
+                self.skip("Token valid");
+            }
+        }
+        self.check_path(account_config.path.clone()).await;
+        Ok(())
+    }
+
+    /// Checks the token and sample requests.
+    async fn check_web_service(&mut self, account: &Account, account_config: &AccountConfig) {
+        let info = match account.get_info().await {
+            Ok(info) => info,
+            Err(err) => {
+                let hint = err
+                    .ws_error()
+                    .and_then(util::ws_error_hint)
+                    .unwrap_or("Check your internet connection and try again later.");
+                self.fail(&format!("Token valid: {err}"), hint);
+                return;
+            }
+        };
+        self.pass(&format!(
+            "Token valid: {} on Moodle {}",
+            info.full_name,
+            info.release.as_deref().unwrap_or("release unknown")
+        ));
+
+        let capabilities = account.capabilities().await;
+        let missing = capabilities
+            .as_deref()
+            .map_or_else(Vec::new, |capabilities| {
+                REQUIRED_FUNCTIONS
+                    .into_iter()
+                    .filter(|function| !capabilities.supports(function))
+                    .collect()
+            });
+        self.check(
+            "Required functions available",
+            missing.is_empty(),
+            &format!(
+                "The mobile app service of the site lacks {}. Ask the site administrators to \
+                 update the service.",
+                missing.join(", ")
+            ),
+        );
+        self.check(
+            "File downloads allowed",
+            info.can_download_files != Some(false),
+            "The site does not allow downloading files with the mobile app. Ask the site \
+             administrators to enable file downloads for the mobile app service.",
+        );
+
+        let course = account_config
+            .courses
+            .0
+            .iter()
+            .find(|(_, course_config)| course_config.sync && !course_config.archived)
+            .or_else(|| account_config.courses.0.iter().next());
+        let (course_id, course_path) = match course {
+            Some((&id, course_config)) => (
+                id,
+                account_config
+                    .path
+                    .join(course_config.name_as_path_component().as_ref()),
+            ),
+            None => match account.get_courses().await {
+                Ok(courses) => match courses.first() {
+                    Some(course) => (course.id, account_config.path.join(&course.full_name)),
+                    None => {
+                        self.skip("Course contents readable: no courses available");
+                        return;
+                    }
+                },
+                Err(err) => {
+                    let hint = err
+                        .ws_error()
+                        .and_then(util::ws_error_hint)
+                        .unwrap_or("Check that you are enrolled in any courses.");
+                    self.fail(&format!("Courses readable: {err}"), hint);
+                    return;
+                }
+            },
+        };
+        match account.get_contents(course_id, course_path).await {
+            Ok(contents) => self.pass(&format!(
+                "Course contents readable: {} files in course {course_id}",
+                contents.count()
+            )),
+            Err(err) => {
+                let hint = err
+                    .ws_error()
+                    .and_then(util::ws_error_hint)
+                    .unwrap_or("Try again later. If the error persists, report it.");
+                self.fail(&format!("Course contents readable: {err}"), hint);
+            }
+        }
+    }
+
+    /// Checks that the download directory is writable.
+    async fn check_path(&mut self, path: PathBuf) {
+        let check = format!("Download directory writable: {}", path.display());
+        let result = task::spawn_blocking(move || -> io::Result<()> {
+            fs::create_dir_all(&path)?;
+            let probe_path = path.join(PROBE_FILE_NAME);
+            fs::write(&probe_path, [])?;
+            fs::remove_file(probe_path)
+        })
+        .await
+        .map_err(io::Error::other)
+        .and_then(|result| result);
+        match result {
+            Ok(()) => self.pass(&check),
+            Err(err) => self.fail(
+                &format!("{check}: {err}"),
+                "Check the permissions of the directory or choose another one with `edu-sync-cli \
+                 account set-path`.",
+            ),
+        }
+    }
+}
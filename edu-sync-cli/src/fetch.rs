@@ -1,4 +1,10 @@
-use edu_sync::{account::Account, config::Config};
+use std::{collections::BTreeMap, future::Future, sync::Arc};
+
+use edu_sync::{
+    account::{ws, Account, Course, SiteCapabilities, Token},
+    config::AccountConfig,
+    rules::CategoryPath,
+};
 
 use crate::util;
 
@@ -8,21 +14,37 @@ pub struct Subcommand {}
 
 impl Subcommand {
     pub async fn run(self) -> anyhow::Result<()> {
-        let mut config = Config::read().await?;
+        let (mut config, mut store) = util::read_config().await?;
 
         if util::check_accounts(&config) {
             let results = config
                 .accounts
-                .values_mut()
-                .map(|account_config| {
-                    let account = Account::new(account_config.id.clone(), account_config.token);
-                    let courses = tokio::spawn(async move { account.get_courses().await });
-                    (account_config, courses)
+                .iter()
+                .map(|(name, account_config)| {
+                    let token = store.get(&account_config.token)?;
+                    let courses = tokio::spawn(request_courses(account_config, token)?);
+                    anyhow::Ok((name.clone(), courses))
                 })
-                .collect::<Vec<_>>();
+                .collect::<Result<Vec<_>, _>>()?;
 
-            for (account_config, courses) in results {
-                account_config.courses.update(courses.await??);
+            for (name, courses) in results {
+                let mut courses = courses.await?;
+                if matches!(&courses, Err(err) if err.is_invalid_token()) {
+                    let Some(token) =
+                        util::renew_token(&mut config, &mut store, &name, false).await
+                    else {
+                        continue;
+                    };
+                    courses = request_courses(&config.accounts[&name], token)?.await;
+                }
+                let (courses, categories, capabilities) = courses?;
+                let account_config = config.accounts.get_mut(&name).unwrap();
+                account_config
+                    .courses
+                    .update(courses, &account_config.rules, &categories);
+                if capabilities.is_some() {
+                    account_config.capabilities = capabilities;
+                }
             }
 
             config.write().await?;
@@ -31,3 +53,30 @@ impl Subcommand {
         Ok(())
     }
 }
+
+type Courses = (
+    Vec<Course>,
+    BTreeMap<u64, CategoryPath>,
+    Option<SiteCapabilities>,
+);
+
+/// Requests the courses of an account, the paths of all categories if its
+/// rules need them, and the current capabilities of the site.
+fn request_courses(
+    account_config: &AccountConfig,
+    token: Token,
+) -> anyhow::Result<impl Future<Output = ws::Result<Courses>> + Send + 'static> {
+    let account =
+        Account::new(account_config.id.clone(), token).with_http(account_config.http.client()?);
+    let needs_categories = account_config.rules.needs_categories();
+    Ok(async move {
+        let categories = if needs_categories {
+            account.get_category_paths().await?
+        } else {
+            BTreeMap::new()
+        };
+        let courses = account.get_courses().await?;
+        let capabilities = account.capabilities().await.map(Arc::unwrap_or_clone);
+        Ok((courses, categories, capabilities))
+    })
+}
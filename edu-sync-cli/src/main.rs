@@ -6,8 +6,12 @@
 #![warn(clippy::semicolon_if_nothing_returned)]
 #![deny(rustdoc::all)]
 
+mod account;
 mod add;
+mod archive;
 mod config;
+mod courses;
+mod doctor;
 mod fetch;
 mod sync;
 mod util;
@@ -21,8 +25,12 @@ use tracing_subscriber::EnvFilter;
 #[derive(Debug, clap::Parser)]
 #[clap(name = "edu-sync-cli", author, about)]
 enum Subcommand {
+    Account(account::Subcommand),
     Add(add::Subcommand),
+    Archive(archive::Subcommand),
     Config(config::Subcommand),
+    Courses(courses::Subcommand),
+    Doctor(doctor::Subcommand),
     Fetch(fetch::Subcommand),
     Sync(sync::Subcommand),
 }
@@ -30,8 +38,12 @@ enum Subcommand {
 impl Subcommand {
     async fn run(self) -> anyhow::Result<()> {
         match self {
+            Subcommand::Account(command) => command.run().await,
             Subcommand::Add(command) => command.run().await,
+            Subcommand::Archive(command) => command.run().await,
             Subcommand::Config(command) => command.run().await,
+            Subcommand::Courses(command) => command.run().await,
+            Subcommand::Doctor(command) => command.run().await,
             Subcommand::Fetch(command) => command.run().await,
             Subcommand::Sync(command) => command.run().await,
         }
@@ -51,5 +63,5 @@ async fn main() -> anyhow::Result<()> {
     }
     setup_panic!();
 
-    Subcommand::parse().run().await
+    Subcommand::parse().run().await.map_err(util::with_hint)
 }
@@ -1,9 +1,11 @@
 use std::{
     borrow::Cow,
+    collections::BTreeMap,
     future::Future,
-    io,
+    io, mem,
+    path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
     time::Duration,
@@ -14,14 +16,16 @@ use dialoguer::{
     Confirm,
 };
 use edu_sync::{
-    account::{Account, Token},
+    account::{Account, LinkIndex, Token, Warning},
     config::{AccountConfig, Config},
     content::{Content, Download, FileDownload, SyncStatus},
+    http,
+    secret::Store,
 };
 use futures_util::{
     future,
     stream::{self, FuturesOrdered, FuturesUnordered},
-    StreamExt, TryFutureExt,
+    StreamExt,
 };
 use indicatif::{BinaryBytes, MultiProgress, ProgressBar, ProgressStyle};
 use tokio::{
@@ -29,6 +33,7 @@ use tokio::{
     time::{self, sleep},
 };
 use tracing::{info, trace};
+use url::Url;
 
 use crate::util;
 
@@ -43,10 +48,13 @@ pub struct Subcommand {
 
 impl Subcommand {
     pub async fn run(self) -> anyhow::Result<()> {
-        let config = Config::read().await?;
+        let (mut config, mut store) = util::read_config().await?;
 
         if util::check_active_courses(&config) {
-            let syncer = Syncer::from(config).await;
+            let mut syncer = Syncer::from(&config, &mut store).await?;
+            syncer
+                .renew_tokens(&mut config, &mut store, self.no_confirm)
+                .await;
             syncer.sync(self.no_confirm).await?;
         }
 
@@ -54,101 +62,274 @@ impl Subcommand {
     }
 }
 
-struct Syncer {
+/// A course to synchronize.
+pub struct SyncCourse {
+    pub account: Arc<Account>,
+    pub id: u64,
+    pub name: String,
+    pub path: PathBuf,
+    /// Whether to also capture the forums and grades of the course.
+    pub archive: bool,
+}
+
+/// The maximum number of courses whose contents are requested at once.
+const BATCH_SIZE: usize = 16;
+
+/// The result of requesting the contents of a course.
+enum Requested {
+    Status(Box<CourseStatus>),
+    /// The token of the account was rejected.
+    InvalidToken(SyncCourse),
+    Failed,
+}
+
+pub struct Syncer {
     parallel_downloads: usize,
     outdated_courses: Vec<CourseStatus>,
+    /// The number of courses whose contents could not be requested.
+    failed_courses: usize,
+    /// The courses whose contents were rejected due to an invalid token.
+    invalid_token_courses: Vec<SyncCourse>,
+    /// The warnings of the web service by course name.
+    warnings: Vec<(String, Warning)>,
+    /// The link indices of all courses, written after downloading.
+    link_indices: Vec<LinkIndex>,
 }
 
 impl Syncer {
-    async fn from(config: Config) -> Self {
-        eprintln!("Requesting content databases...");
-        let parallel_downloads = config.parallel_downloads;
-        let outdated_courses = config
+    async fn from(config: &Config, store: &mut Store) -> anyhow::Result<Self> {
+        let accounts = config
             .accounts
+            .values()
+            .map(|account_config| {
+                let token = store.get(&account_config.token)?;
+                let http = account_config.http.client()?;
+                anyhow::Ok((account_config, token, http))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let courses = accounts
             .into_iter()
-            .flat_map(|(_account_name, account_config)| {
+            .flat_map(|(account_config, token, http)| {
                 let AccountConfig {
                     path,
                     courses,
                     id,
-                    token,
+                    text_format,
+                    url_format,
+                    link_index,
+                    capabilities,
                     ..
                 } = account_config;
-                let account = Account::new(id, token);
+                let account = Account::new(id.clone(), token)
+                    .with_http(http)
+                    .with_capabilities(capabilities.clone())
+                    .with_text_format(*text_format)
+                    .with_url_format(*url_format)
+                    .with_link_index(*link_index);
                 let account = Arc::new(account);
                 courses
                     .0
-                    .into_iter()
+                    .iter()
                     .rev()
-                    .filter(|(_, course_config)| course_config.sync)
-                    .map(move |(course_id, course_config)| {
-                        let course_path =
-                            path.join(course_config.name_as_path_component().as_ref());
-                        (account.clone(), course_id, course_config.name, course_path)
+                    .filter(|(_, course_config)| course_config.sync && !course_config.archived)
+                    .map(move |(&course_id, course_config)| SyncCourse {
+                        account: account.clone(),
+                        id: course_id,
+                        path: path.join(course_config.name_as_path_component().as_ref()),
+                        name: course_config.name.clone(),
+                        archive: false,
                     })
             })
-            .map(|(account, course_id, course_name, course_path)| {
-                tokio::spawn(async move {
-                    let fetch_status = |course_path, course_name| async {
-                        account
-                            .get_contents(course_id, course_path)
-                            .and_then(|contents| async {
-                                let status = CourseStatus::from_contents(
-                                    contents,
-                                    account.token(),
-                                    course_name,
-                                )
-                                .await;
-                                Ok(status)
-                            })
-                            .await
-                    };
+            .collect();
+        Ok(Self::new(config.parallel_downloads, courses).await)
+    }
+
+    /// Renews the tokens the site rejected by prompting for the passwords of
+    /// the accounts and requests the contents of their courses again.
+    ///
+    /// Courses of accounts whose token could not be renewed are skipped.
+    pub async fn renew_tokens(&mut self, config: &mut Config, store: &mut Store, no_confirm: bool) {
+        let mut accounts = BTreeMap::<String, Option<Arc<Account>>>::new();
+        let mut courses = Vec::new();
+        for course in mem::take(&mut self.invalid_token_courses) {
+            let name = course.account.id().to_string();
+            let account = match accounts.get(&name) {
+                Some(account) => account.clone(),
+                None => {
+                    let account = util::renew_token(config, store, &name, no_confirm)
+                        .await
+                        .map(|token| Arc::new(course.account.as_ref().clone().with_token(token)));
+                    accounts.insert(name, account.clone());
+                    account
+                }
+            };
+            match account {
+                Some(account) => courses.push(SyncCourse { account, ..course }),
+                None => self.failed_courses += 1,
+            }
+        }
+        if courses.is_empty() {
+            return;
+        }
+
+        let renewed = Self::new(self.parallel_downloads, courses).await;
+        self.outdated_courses.extend(renewed.outdated_courses);
+        self.failed_courses += renewed.failed_courses + renewed.invalid_token_courses.len();
+        self.warnings.extend(renewed.warnings);
+        self.link_indices.extend(renewed.link_indices);
+    }
+
+    /// Requests the contents of the courses and determines what is outdated.
+    ///
+    /// The contents of the courses of an account are requested in batches.
+    pub async fn new(parallel_downloads: usize, courses: Vec<SyncCourse>) -> Self {
+        eprintln!("Requesting content databases...");
+        let mut batches = Vec::<(Arc<Account>, Vec<SyncCourse>)>::new();
+        for course in courses {
+            match batches.last_mut() {
+                Some((account, batch))
+                    if Arc::ptr_eq(account, &course.account) && batch.len() < BATCH_SIZE =>
+                {
+                    batch.push(course);
+                }
+                _ => batches.push((course.account.clone(), vec![course])),
+            }
+        }
 
+        let statuses = batches
+            .into_iter()
+            .map(|(account, batch)| {
+                tokio::spawn(async move {
                     let account_id = account.id();
-                    let mut status = fetch_status(course_path.clone(), course_name.clone()).await;
+                    let courses = batch
+                        .iter()
+                        .map(|course| (course.id, course.path.clone()))
+                        .collect::<Vec<_>>();
+                    let mut results = account.get_contents_batch(&courses).await;
                     for _ in 0..4 {
-                        match &status {
-                            Ok(_) => break,
+                        match &results {
                             Err(err) if err.is_http() => {
                                 sleep(Duration::from_millis(100)).await;
                                 eprintln!(
-                                    "Could not get contents for {course_name} from {account_id} \
-                                     ({err}). Retrying."
+                                    "Could not get contents from {account_id} ({err}). Retrying."
                                 );
-                                status =
-                                    fetch_status(course_path.clone(), course_name.clone()).await;
+                                results = account.get_contents_batch(&courses).await;
                             }
-                            Err(_) => break,
+                            _ => break,
                         }
                     }
-
-                    match status {
-                        Ok(ok) => Some(ok),
+                    let results = match results {
+                        Ok(results) => results,
+                        Err(err) if err.is_invalid_token() => {
+                            return batch.into_iter().map(Requested::InvalidToken).collect();
+                        }
                         Err(err) => {
                             eprintln!(
-                                "Could not get contents for {course_name} from {account_id} \
-                                 ({err}). Giving up."
+                                "Could not get contents from {account_id} ({err}). Giving up."
                             );
-                            None
+                            if let Some(hint) = err.ws_error().and_then(util::ws_error_hint) {
+                                eprintln!("{hint}");
+                            }
+                            return batch.iter().map(|_| Requested::Failed).collect();
+                        }
+                    };
+
+                    let mut statuses = Vec::with_capacity(batch.len());
+                    for (course, contents) in batch.into_iter().zip(results) {
+                        let contents = match contents {
+                            Ok(mut contents) if course.archive => account
+                                .get_archive_contents(course.id, course.path)
+                                .await
+                                .map(|archive_contents| {
+                                    contents.contents.extend(archive_contents.data);
+                                    (contents, archive_contents.warnings)
+                                }),
+                            contents => contents.map(|contents| (contents, Vec::new())),
+                        };
+                        match contents {
+                            Ok((contents, warnings)) => {
+                                let mut status = CourseStatus::from_contents(
+                                    contents.contents.into_iter(),
+                                    account.id().site_url.clone(),
+                                    account.http().clone(),
+                                    account.token(),
+                                    course.name,
+                                )
+                                .await;
+                                status.warnings = warnings;
+                                status.link_index = contents.link_index;
+                                statuses.push(Requested::Status(Box::new(status)));
+                            }
+                            Err(err) => {
+                                eprintln!(
+                                    "Could not get contents for {} from {account_id} ({err}). \
+                                     Giving up.",
+                                    course.name
+                                );
+                                if let Some(hint) = err.ws_error().and_then(util::ws_error_hint) {
+                                    eprintln!("{hint}");
+                                }
+                                statuses.push(Requested::Failed);
+                            }
                         }
                     }
+                    statuses
                 })
             })
             .collect::<FuturesOrdered<_>>()
-            .filter_map(|res| async move { res.inspect_err(|err| eprintln!("{err}")).ok() })
-            .filter_map(|res| async move { res })
-            .filter(|course_status| future::ready(!course_status.downloads.is_empty()))
+            .map(|res| {
+                res.unwrap_or_else(|err| {
+                    eprintln!("{err}");
+                    vec![Requested::Failed]
+                })
+            })
             .collect::<Vec<_>>()
-            .await;
-        Self {
+            .await
+            .into_iter()
+            .flatten();
+
+        let mut syncer = Self {
             parallel_downloads,
-            outdated_courses,
+            outdated_courses: Vec::new(),
+            failed_courses: 0,
+            invalid_token_courses: Vec::new(),
+            warnings: Vec::new(),
+            link_indices: Vec::new(),
+        };
+        for requested in statuses {
+            match requested {
+                Requested::Status(mut status) => {
+                    syncer.link_indices.extend(status.link_index.take());
+                    syncer.warnings.extend(
+                        status
+                            .warnings
+                            .iter()
+                            .map(|warning| (status.name.clone(), warning.clone())),
+                    );
+                    if !status.downloads.is_empty() {
+                        syncer.outdated_courses.push(*status);
+                    }
+                }
+                Requested::InvalidToken(course) => syncer.invalid_token_courses.push(course),
+                Requested::Failed => syncer.failed_courses += 1,
+            }
         }
+        syncer
     }
 
-    async fn sync(self, no_confirm: bool) -> anyhow::Result<()> {
-        if self.outdated_courses.is_empty() {
+    /// Downloads the outdated contents after asking for confirmation and
+    /// writes the link indices.
+    ///
+    /// Returns whether all courses are up to date afterwards.
+    pub async fn sync(mut self, no_confirm: bool) -> anyhow::Result<bool> {
+        let complete = self.failed_courses == 0 && self.invalid_token_courses.is_empty();
+        for (course_name, warning) in &self.warnings {
+            eprintln!("Warning for {course_name}: {warning}");
+        }
+        let link_indices = mem::take(&mut self.link_indices);
+        let synced = if self.outdated_courses.is_empty() {
             eprintln!("All resources are up to date.");
+            true
         } else {
             eprintln!();
 
@@ -206,16 +387,32 @@ impl Syncer {
                 })
                 .await??;
 
+            let mut failed_downloads = 0;
             if proceed {
                 eprintln!("Downloading missing files...");
-                self.download().await?;
+                failed_downloads = self.download().await?;
+                if failed_downloads > 0 {
+                    eprintln!("{failed_downloads} items could not be downloaded.");
+                }
+            }
+            proceed && failed_downloads == 0
+        };
+
+        let mut indexed = true;
+        for link_index in link_indices {
+            let path = link_index.path().display().to_string();
+            if let Err(err) = link_index.write().await {
+                eprintln!("Could not write the link index of {path}: {err}");
+                indexed = false;
             }
         }
-
-        Ok(())
+        Ok(complete && synced && indexed)
     }
 
-    async fn download(self) -> io::Result<()> {
+    /// Downloads the outdated contents.
+    ///
+    /// Returns the number of items that could not be downloaded.
+    async fn download(self) -> io::Result<usize> {
         let multi_progress = Arc::new(MultiProgress::new());
         let content_progress_style = ProgressStyle::default_bar()
             .template("[{pos}/{len}] {wide_msg}")
@@ -234,9 +431,12 @@ impl Syncer {
             .into_iter()
             .map(
                 |CourseStatus {
+                     site_url,
+                     http,
                      token,
                      name,
                      downloads,
+                     ..
                  }| {
                     let multi_progress = multi_progress_clone.clone();
                     let content_progress_style = content_progress_style.clone();
@@ -251,6 +451,8 @@ impl Syncer {
                     tokio::spawn(async move {
                         CourseDownload {
                             downloads,
+                            site_url,
+                            http,
                             token,
                             content_progress,
                             size_progress,
@@ -274,17 +476,35 @@ impl Syncer {
             ),
         );
 
-        let (file_downloads, content_downloads, size_progress, content_progress, size) =
+        let failed_courses = AtomicUsize::new(0);
+        let (file_downloads, content_downloads, size_progress, content_progress, failed, size) =
             download_tasks
-                .filter_map(|res| future::ready(res.map_err(|err| eprintln!("{}", err)).ok()))
-                .filter_map(|res| future::ready(res.map_err(|err| eprintln!("{}", err)).ok()))
+                .filter_map(|res| {
+                    future::ready(
+                        res.map_err(io::Error::from)
+                            .and_then(|res| res)
+                            .map_err(|err| {
+                                failed_courses.fetch_add(1, Ordering::Relaxed);
+                                eprintln!("{}", err);
+                            })
+                            .ok(),
+                    )
+                })
                 .fold(
-                    (Vec::new(), Vec::new(), Vec::new(), Vec::new(), 0),
+                    (
+                        Vec::new(),
+                        Vec::new(),
+                        Vec::new(),
+                        Vec::new(),
+                        Vec::new(),
+                        0,
+                    ),
                     |(
                         mut file_downloads,
                         mut content_downloads,
                         mut size_progress,
                         mut content_progress,
+                        mut failed,
                         size,
                     ),
                      mut download| async move {
@@ -292,11 +512,13 @@ impl Syncer {
                         content_downloads.append(&mut download.content_downloads);
                         size_progress.push((download.download_progresses, download.size_progress));
                         content_progress.push(download.content_progress);
+                        failed.push(download.failed);
                         (
                             file_downloads,
                             content_downloads,
                             size_progress,
                             content_progress,
+                            failed,
                             size + download.size,
                         )
                     },
@@ -353,29 +575,41 @@ impl Syncer {
         total_bar.finish();
 
         for file_download in file_downloads {
-            file_download??;
+            file_download?;
         }
 
-        Ok(())
+        let failed_downloads = failed
+            .iter()
+            .map(|failed| failed.load(Ordering::Relaxed))
+            .sum::<usize>();
+        Ok(failed_downloads + failed_courses.into_inner())
     }
 }
 
 struct CourseStatus {
+    site_url: Url,
+    /// The link index of the course, which is written after downloading.
+    link_index: Option<LinkIndex>,
+    http: http::Client,
     token: Token,
     name: String,
     downloads: Vec<Download>,
+    warnings: Vec<Warning>,
 }
 
 impl CourseStatus {
     async fn from_contents(
         contents: impl Iterator<Item = Content> + Send,
+        site_url: Url,
+        http: http::Client,
         token: Token,
         name: String,
     ) -> Self {
         let downloads = contents
             .map(|content| {
+                let site_url = site_url.clone();
                 tokio::spawn(async move {
-                    match content.sync().await {
+                    match content.sync(&site_url).await {
                         SyncStatus::Downloadable(download) => Some(download),
                         SyncStatus::NotSupported(content_type, path) => {
                             info!(
@@ -400,15 +634,21 @@ impl CourseStatus {
             .flatten()
             .collect::<Vec<_>>();
         Self {
+            site_url,
+            link_index: None,
+            http,
             token,
             name,
             downloads,
+            warnings: Vec::new(),
         }
     }
 }
 
 struct CourseDownload {
     downloads: Vec<Download>,
+    site_url: Url,
+    http: http::Client,
     token: Token,
     content_progress: ProgressBar,
     size_progress: ProgressBar,
@@ -421,21 +661,25 @@ struct CourseDownloads<F, C> {
     size_progress: ProgressBar,
     size: u64,
     content_progress: ProgressBar,
+    /// The number of downloads that failed.
+    failed: Arc<AtomicUsize>,
 }
 
 impl CourseDownload {
     async fn run(
         self,
-    ) -> io::Result<CourseDownloads<impl Future<Output = io::Result<()>>, impl Future<Output = ()>>>
-    {
+    ) -> io::Result<CourseDownloads<impl Future<Output = ()>, impl Future<Output = ()>>> {
         let Self {
             downloads,
+            site_url,
+            http,
             token,
             content_progress,
             size_progress,
         } = self;
 
         content_progress.set_length(downloads.len() as u64);
+        let failed = Arc::new(AtomicUsize::new(0));
 
         let (file_downloads, content_downloads) = downloads
             .into_iter()
@@ -462,20 +706,16 @@ impl CourseDownload {
             .zip(progresses.iter().cloned())
             .map(|(mut file_download, progress)| {
                 let content_progress = content_progress_clone.clone();
+                let failed = failed.clone();
+                let site_url = site_url.clone();
+                let http = http.clone();
                 async move {
-                    file_download
-                        .run(&token, |val| progress.store(val, Ordering::Relaxed))
-                        .await
-                        .map(|()| {
-                            content_progress.inc(1);
-                            let path = file_download.path().display();
-                            content_progress.println(path.to_string());
-                        })
-                        .inspect_err(|err| {
-                            let path = file_download.path().display();
-                            content_progress
-                                .println(format!("error while downloading {path}: {err}"));
+                    let res = file_download
+                        .run(&http, &site_url, &token, |val| {
+                            progress.store(val, Ordering::Relaxed);
                         })
+                        .await;
+                    report(&content_progress, &failed, file_download.path(), res);
                 }
             })
             .collect::<Vec<_>>();
@@ -485,20 +725,23 @@ impl CourseDownload {
             .into_iter()
             .map(|download| {
                 let content_progress = content_progress_clone.clone();
+                let failed = failed.clone();
+                let site_url = site_url.clone();
+                let http = http.clone();
                 async move {
                     match download {
                         Download::File(_) => unreachable!(),
                         Download::Url(mut url_download) => {
-                            url_download.run().await.unwrap();
-                            content_progress.inc(1);
-                            let path = url_download.path().display().to_string();
-                            content_progress.println(path);
+                            let res = url_download.run().await;
+                            report(&content_progress, &failed, url_download.path(), res);
                         }
                         Download::Content(mut content_download) => {
-                            content_download.run().await.unwrap();
-                            content_progress.inc(1);
-                            let path = content_download.path().display().to_string();
-                            content_progress.println(path);
+                            let res = content_download.run().await;
+                            report(&content_progress, &failed, content_download.path(), res);
+                        }
+                        Download::Html(mut html_download) => {
+                            let res = html_download.run(&http, &site_url, &token).await;
+                            report(&content_progress, &failed, html_download.path(), res);
                         }
                     }
                 }
@@ -512,6 +755,158 @@ impl CourseDownload {
             size_progress,
             size: download_size,
             content_progress,
+            failed,
         })
     }
 }
+
+/// Reports the result of a download and counts it if it failed.
+fn report(content_progress: &ProgressBar, failed: &AtomicUsize, path: &Path, res: io::Result<()>) {
+    let path = path.display();
+    match res {
+        Ok(()) => {
+            content_progress.inc(1);
+            content_progress.println(path.to_string());
+        }
+        Err(err) => {
+            failed.fetch_add(1, Ordering::Relaxed);
+            content_progress.println(format!("error while downloading {path}: {err}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use edu_sync::{
+        account::Account,
+        config::{AccountConfig, Config},
+        http::HttpConfig,
+        rules::Rules,
+        secret::{Backend, Store},
+    };
+    use edu_ws_mock::{Fixture, MockServer};
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// The files below `dir` with their content, sorted by path.
+    fn files(dir: &Path) -> Vec<(PathBuf, String)> {
+        let mut dirs = vec![dir.to_path_buf()];
+        let mut files = Vec::new();
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    let content = fs::read_to_string(&path).unwrap_or_default();
+                    files.push((path, content));
+                }
+            }
+        }
+        files.sort();
+        files
+    }
+
+    /// Syncs all courses of `config` without confirmation and returns the new
+    /// files.
+    async fn sync(config: &Config, store: &mut Store, dir: &Path) -> Vec<(PathBuf, String)> {
+        let before = files(dir);
+        let syncer = Syncer::from(config, store).await.unwrap();
+        assert!(syncer.sync(true).await.unwrap());
+        files(dir)
+            .into_iter()
+            .filter(|file| !before.contains(file))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_add_fetch_sync() {
+        let server = MockServer::start(Fixture::example()).await.unwrap();
+        let dir = TempDir::new().unwrap();
+
+        // add
+        let http_config = HttpConfig {
+            user_agent: Some("Edu Sync Test".to_string()),
+            ..HttpConfig::default()
+        };
+        let http = http_config.client().unwrap();
+        let public_config = Account::public_config(http.clone(), server.url())
+            .await
+            .unwrap();
+        assert!(public_config.mobile_service);
+        let response = Account::login(http.clone(), server.url(), "student", "secret")
+            .await
+            .unwrap();
+        let mut account_config = AccountConfig::new(
+            http.clone(),
+            server.url().clone(),
+            response.token,
+            dir.path().to_path_buf(),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(account_config.user, "Sam Student");
+        account_config.http = http_config;
+
+        // fetch
+        let account = Account::new(account_config.id.clone(), response.token)
+            .with_http(account_config.http.client().unwrap());
+        let courses = account.get_courses().await.unwrap();
+        let categories = account.get_category_paths().await.unwrap();
+        account_config
+            .courses
+            .update(courses, &Rules::default(), &categories);
+        assert_eq!(account_config.courses.0.len(), 2);
+        account_config.courses.0.get_mut(&42).unwrap().sync = true;
+        let course_path = dir.path().join(
+            account_config.courses.0[&42]
+                .name_as_path_component()
+                .as_ref(),
+        );
+        let mut config = Config::default();
+        config
+            .accounts
+            .insert(account_config.id.to_string(), account_config);
+        let mut store = Store::new(Backend::Plaintext);
+
+        // sync
+        let synced = sync(&config, &mut store, dir.path()).await;
+        let (notes_path, notes) = synced
+            .iter()
+            .find(|(path, _)| path.ends_with("notes.pdf"))
+            .unwrap()
+            .clone();
+        assert!(notes_path.starts_with(&course_path));
+        assert_eq!(notes, "Lecture notes, version 1");
+        assert!(synced.iter().any(|(path, _)| path.ends_with("sheet 1.pdf")));
+        assert!(!synced.iter().any(|(path, _)| path.ends_with("script.pdf")));
+
+        assert!(sync(&config, &mut store, dir.path()).await.is_empty());
+
+        server.update(|fixture| {
+            let file = fixture
+                .course_mut(42)
+                .unwrap()
+                .file_mut(10, "notes.pdf")
+                .unwrap();
+            file.content = "Lecture notes, version 2".to_string();
+            file.modified += 60;
+        });
+        // Changed files are saved next to the previous version.
+        let synced = sync(&config, &mut store, dir.path()).await;
+        assert_eq!(synced.len(), 1);
+        let (path, content) = &synced[0];
+        assert_ne!(path, &notes_path);
+        assert_eq!(path.parent(), notes_path.parent());
+        assert_eq!(content, "Lecture notes, version 2");
+        assert_eq!(
+            fs::read_to_string(&notes_path).unwrap(),
+            "Lecture notes, version 1"
+        );
+        assert!(sync(&config, &mut store, dir.path()).await.is_empty());
+    }
+}
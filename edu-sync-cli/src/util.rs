@@ -1,4 +1,123 @@
-use edu_sync::config::Config;
+use std::{
+    env,
+    io::{self, IsTerminal},
+};
+
+use anyhow::bail;
+use dialoguer::Password;
+use edu_sync::{
+    account::{ws, Account, Token},
+    config::{AccountConfig, Config, CourseSelector},
+    secret::{self, Store},
+};
+use tokio::task;
+
+use crate::add::{self, Method};
+
+/// The environment variable to read the passphrase of the encrypted token file
+/// from.
+const PASSPHRASE_VAR: &str = "EDU_SYNC_PASSPHRASE";
+
+/// Reads the config and moves its tokens into the configured backend.
+pub async fn read_config() -> anyhow::Result<(Config, Store)> {
+    let mut config = Config::read().await?;
+    let mut store = secret_store(&config).await?;
+    let old_secret_refs = config.store_tokens(&mut store)?;
+    if !old_secret_refs.is_empty() {
+        config.write().await?;
+        store.remove_all(&old_secret_refs)?;
+    }
+    Ok((config, store))
+}
+
+/// Tells the user how to renew an invalid token.
+const INVALID_TOKEN_HINT: &str = "The token is no longer valid. Run fetch or sync to log in \
+                                  again, or add the account again with `edu-sync-cli add`.";
+
+/// Renews the invalid token of the account named `name` by logging in again
+/// the way the site offers, such as with the password or via SSO, and writes
+/// the config.
+///
+/// The account is skipped without prompting if `no_confirm` is set or stdin is
+/// not a terminal. Returns the new token, or `None` after reporting why the
+/// token could not be renewed.
+pub async fn renew_token(
+    config: &mut Config,
+    store: &mut Store,
+    name: &str,
+    no_confirm: bool,
+) -> Option<Token> {
+    let account_config = config.accounts.get(name)?;
+    if no_confirm || !io::stdin().is_terminal() {
+        eprintln!("Skipping {account_config}, as its token is invalid.");
+        eprintln!("{INVALID_TOKEN_HINT}");
+        return None;
+    }
+
+    eprintln!("The token of {account_config} is invalid. Log in again to renew it.");
+    match login_again(config, store, name).await {
+        Ok(token) => Some(token),
+        Err(err) => {
+            eprintln!("Could not renew the token of {name}: {err:#}");
+            eprintln!(
+                "If you log in via a QR code, add the account again with `edu-sync-cli add --qr`."
+            );
+            None
+        }
+    }
+}
+
+/// Logs into the account named `name` again.
+///
+/// The private token cannot renew the token: Moodle only hands out autologin
+/// keys for it to requests authenticated with a valid token.
+async fn login_again(config: &mut Config, store: &mut Store, name: &str) -> anyhow::Result<Token> {
+    let account_config = config.accounts.get_mut(name).unwrap();
+    let http = account_config.http.client()?;
+    let site_url = account_config.id.site_url.clone();
+    let public_config = Account::public_config(http.clone(), &site_url).await.ok();
+    let method = match &public_config {
+        Some(public_config) => add::choose_method(public_config).await?,
+        None => Method::Password(None),
+    };
+    let method = match method {
+        Method::Password(None) => Method::Password(account_config.username.clone()),
+        method => method,
+    };
+    let lang = account_config.id.lang.clone();
+    let (token, private_token) =
+        add::log_in(method, http, &site_url, public_config, false, lang).await?;
+    account_config.set_tokens(store, token, private_token)?;
+    config.write().await?;
+    Ok(token)
+}
+
+/// Creates the token store for the config, asking for a passphrase if needed.
+pub async fn secret_store(config: &Config) -> anyhow::Result<Store> {
+    let store = Store::new(config.secret_backend);
+    if !config.needs_passphrase() {
+        return Ok(store);
+    }
+
+    let passphrase = match env::var(PASSPHRASE_VAR) {
+        Ok(passphrase) => passphrase,
+        Err(_) => {
+            let new = !secret::Store::path().exists();
+            task::spawn_blocking(move || {
+                let password = Password::new().with_prompt("Passphrase for the token file");
+                if new {
+                    password
+                        .with_confirmation("Repeat passphrase", "Passphrases do not match")
+                        .interact()
+                } else {
+                    password.interact()
+                }
+            })
+            .await??
+        }
+    };
+    Ok(store.with_passphrase(passphrase))
+}
 
 pub fn check_accounts(config: &Config) -> bool {
     let sucess = config.has_accounts();
@@ -24,3 +143,105 @@ pub fn check_active_courses(config: &Config) -> bool {
         true
     }
 }
+
+/// Looks up an account by its name as shown by `account list`.
+pub fn account_config<'a>(
+    config: &'a mut Config,
+    account: &str,
+) -> anyhow::Result<&'a mut AccountConfig> {
+    if !config.accounts.contains_key(account) {
+        let accounts = config
+            .accounts
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+        bail!("no account {account:?} configured. Known accounts: {accounts}");
+    }
+    Ok(config.accounts.get_mut(account).unwrap())
+}
+
+/// Returns the accounts selected by the name shown by `account list`, or all
+/// accounts.
+pub fn accounts<'a>(
+    config: &'a mut Config,
+    account: Option<&str>,
+) -> anyhow::Result<Vec<(&'a String, &'a mut AccountConfig)>> {
+    if let Some(account) = account {
+        account_config(config, account)?;
+    }
+    let accounts = config
+        .accounts
+        .iter_mut()
+        .filter(|(name, _)| account.is_none_or(|account| account == name.as_str()))
+        .collect();
+    Ok(accounts)
+}
+
+/// Looks up the only course matching `selector`.
+///
+/// Returns the name of the account and the id of the course.
+pub fn unique_course(
+    config: &mut Config,
+    account: Option<&str>,
+    selector: &CourseSelector,
+) -> anyhow::Result<(String, u64)> {
+    let matches = accounts(config, account)?
+        .into_iter()
+        .flat_map(|(name, account_config)| {
+            account_config
+                .courses
+                .0
+                .iter()
+                .filter(|(&id, course_config)| selector.matches(id, course_config))
+                .map(move |(&id, _)| (name.clone(), id))
+        })
+        .collect::<Vec<_>>();
+    match <[_; 1]>::try_from(matches) {
+        Ok([course]) => Ok(course),
+        Err(matches) if matches.is_empty() => {
+            bail!("No course matches {selector}. To update the courses, use fetch.")
+        }
+        Err(_) => bail!("{selector} matches multiple courses."),
+    }
+}
+
+/// Tells the user how to resolve a web service error, if possible.
+pub fn ws_error_hint(err: &ws::Error) -> Option<&'static str> {
+    let hint = match err {
+        ws::Error::InvalidToken { .. } | ws::Error::ServiceRequiresLogin { .. } => {
+            INVALID_TOKEN_HINT
+        }
+        ws::Error::RequireLogin { .. } => {
+            "Log in to the site in a browser and complete any pending steps, such as accepting the \
+             site policy, then try again."
+        }
+        ws::Error::SiteMaintenance { .. } => "The site is in maintenance mode. Try again later.",
+        ws::Error::WebServicesNotEnabled { .. } | ws::Error::ServiceNotAvailable { .. } => {
+            "The site does not provide the mobile app web service. Ask the site administrators to \
+             enable mobile services."
+        }
+        ws::Error::AccessException { .. } | ws::Error::NoPermissions { .. } => {
+            "Your account lacks the permission for this request. Check that you are still enrolled \
+             in the course."
+        }
+        ws::Error::CourseContextNotValid { .. } | ws::Error::InvalidRecord { .. } => {
+            "The course may have been removed or hidden. To update the courses, use fetch."
+        }
+        ws::Error::InvalidParam { .. } | ws::Error::Other { .. } => return None,
+    };
+    Some(hint)
+}
+
+/// Adds a hint to an error caused by a web service error.
+pub fn with_hint(err: anyhow::Error) -> anyhow::Error {
+    let hint = err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<ws::RequestError>())
+        .and_then(ws::RequestError::ws_error)
+        .and_then(ws_error_hint);
+    match hint {
+        Some(hint) => err.context(hint),
+        None => err,
+    }
+}
@@ -1,20 +1,44 @@
-use std::{fmt, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-pub use edu_ws::token::Token;
+pub use crate::links::LinkIndex;
 use edu_ws::{
     ajax,
-    response::{course::Course, info::Info},
+    response::{
+        category::Category,
+        content::{Content as WsContent, Module, Section, Type},
+        forum::{Forum, Post},
+        grade::{GradeItem, UserGrades},
+        info::Info,
+        SummaryFormat,
+    },
     token::{
         login,
         sso::{self, SSOTokenBuilder},
     },
+};
+pub use edu_ws::{
+    capabilities::SiteCapabilities,
+    response::{config as public_config, course::Course, Warning, WithWarnings},
+    token::{qr, Token},
     ws,
 };
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use time::macros::format_description;
+use tokio::sync::OnceCell;
+use tracing::{debug, warn};
 use url::Url;
 
 use crate::{
-    content::Content,
+    content::{self, Content, TextFormat, UrlFormat},
+    html,
+    links::{self, Document, Link, Origin},
+    rules::{self, CategoryPath},
     util::{self, sanitize_path_component},
 };
 
@@ -36,19 +60,43 @@ impl fmt::Display for Id {
 pub struct Account {
     id: Id,
     token: Token,
+    text_format: TextFormat,
+    url_format: UrlFormat,
+    link_index: bool,
+    http: reqwest::Client,
+    /// The capabilities of the site, cached or determined on first use.
+    capabilities: Arc<OnceCell<Arc<SiteCapabilities>>>,
 }
 
 impl Account {
     pub async fn login(
+        http: reqwest::Client,
         site_url: &Url,
         username: &str,
         password: &str,
     ) -> login::Result<login::Response> {
-        login::Client::new(util::shared_http(), site_url)
+        login::Client::new(http, site_url)
             .login(username, password)
             .await
     }
 
+    /// Retrieves the public configuration of a site, which does not require
+    /// logging in.
+    pub async fn public_config(
+        http: reqwest::Client,
+        site_url: &Url,
+    ) -> Result<public_config::Config, ajax::Error> {
+        ajax::Client::new(http, site_url).get_config().await
+    }
+
+    /// Exchanges the content of a QR login code for a token.
+    pub async fn qr_login(
+        http: reqwest::Client,
+        payload: &qr::Payload,
+    ) -> Result<login::Response, ajax::Error> {
+        payload.exchange(http).await
+    }
+
     #[must_use]
     pub const fn id(&self) -> &Id {
         &self.id
@@ -60,78 +108,654 @@ impl Account {
     }
 
     #[must_use]
-    pub const fn new(id: Id, token: Token) -> Self {
-        Self { id, token }
+    pub fn new(id: Id, token: Token) -> Self {
+        Self {
+            id,
+            token,
+            text_format: TextFormat::Html,
+            url_format: UrlFormat::Html,
+            link_index: true,
+            http: util::shared_http(),
+            capabilities: Arc::default(),
+        }
+    }
+
+    /// Replaces the token, such as after renewing it.
+    #[must_use]
+    pub fn with_token(mut self, token: Token) -> Self {
+        self.token = token;
+        self.capabilities = Arc::default();
+        self
     }
 
-    fn ws_client(&self) -> ws::Client {
-        ws::Client::new(
-            util::shared_http(),
+    /// Sets the capabilities of the site, such as those cached in
+    /// [`AccountConfig`](crate::config::AccountConfig), to avoid retrieving
+    /// the site info again.
+    #[must_use]
+    pub fn with_capabilities(mut self, capabilities: Option<SiteCapabilities>) -> Self {
+        self.capabilities = Arc::new(OnceCell::new_with(capabilities.map(Arc::new)));
+        self
+    }
+
+    /// Sets the format to export HTML content in.
+    #[must_use]
+    pub const fn with_text_format(mut self, text_format: TextFormat) -> Self {
+        self.text_format = text_format;
+        self
+    }
+
+    /// Sets the format to write URL modules in.
+    #[must_use]
+    pub const fn with_url_format(mut self, url_format: UrlFormat) -> Self {
+        self.url_format = url_format;
+        self
+    }
+
+    /// Sets whether to write a link index per course.
+    #[must_use]
+    pub const fn with_link_index(mut self, link_index: bool) -> Self {
+        self.link_index = link_index;
+        self
+    }
+
+    /// Sets the HTTP client, such as one created from
+    /// [`HttpConfig`](crate::http::HttpConfig).
+    #[must_use]
+    pub fn with_http(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// The HTTP client for requests of this account, including downloads.
+    #[must_use]
+    pub const fn http(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    /// A client that does not know the capabilities of the site.
+    fn plain_ws_client(&self) -> ws::Client {
+        util::ws_client(
+            self.http.clone(),
             &self.id.site_url,
             self.token,
             self.id.lang.clone(),
         )
     }
 
+    async fn ws_client(&self) -> ws::Client {
+        let ws_client = self.plain_ws_client();
+        match self.capabilities().await {
+            Some(capabilities) => ws_client.with_capabilities(capabilities),
+            None => ws_client,
+        }
+    }
+
+    /// The capabilities of the site, retrieved from the site info unless
+    /// cached.
+    ///
+    /// Returns `None` if the site info could not be retrieved. The next call
+    /// tries again.
+    pub async fn capabilities(&self) -> Option<Arc<SiteCapabilities>> {
+        self.capabilities
+            .get_or_try_init(|| async {
+                let info = self.plain_ws_client().get_info().await?;
+                ws::Result::Ok(Arc::new(SiteCapabilities::from(&info)))
+            })
+            .await
+            .inspect_err(|err| debug!(%err, "Could not determine the site capabilities"))
+            .ok()
+            .cloned()
+    }
+
+    /// Whether the site provides the web service `function`.
+    ///
+    /// Returns `true` if the capabilities of the site are unknown.
+    pub async fn supports(&self, function: &str) -> bool {
+        self.capabilities()
+            .await
+            .is_none_or(|capabilities| capabilities.supports(function))
+    }
+
+    pub async fn get_info(&self) -> ws::Result<Info> {
+        let info = self.plain_ws_client().get_info().await?;
+        let _ = self
+            .capabilities
+            .set(Arc::new(SiteCapabilities::from(&info)));
+        Ok(info)
+    }
+
     pub async fn get_courses(&self) -> ws::Result<Vec<Course>> {
-        let ws_client = self.ws_client();
+        let ws_client = self.ws_client().await;
         ws_client.get_courses(self.id.user_id, false).await
     }
 
+    pub async fn get_categories(&self) -> ws::Result<Vec<Category>> {
+        self.ws_client().await.get_categories().await
+    }
+
+    /// Retrieves the paths of all categories by their id.
+    pub async fn get_category_paths(&self) -> ws::Result<BTreeMap<u64, CategoryPath>> {
+        let categories = self.get_categories().await?;
+        Ok(rules::category_paths(categories))
+    }
+
     pub async fn get_contents(
         &self,
         course_id: u64,
         course_path: PathBuf,
     ) -> ws::Result<impl Iterator<Item = Content>> {
-        let contents = self
+        let sections = self.ws_client().await.get_contents(course_id).await?;
+        Ok(self.contents(sections, course_path).contents.into_iter())
+    }
+
+    /// Retrieves the contents of several courses, given by their id and path,
+    /// in a single request.
+    ///
+    /// Returns the results in the order of `courses`.
+    pub async fn get_contents_batch(
+        &self,
+        courses: &[(u64, PathBuf)],
+    ) -> ws::Result<Vec<ws::Result<CourseContents>>> {
+        let course_ids = courses
+            .iter()
+            .map(|(course_id, _)| *course_id)
+            .collect::<Vec<_>>();
+        let results = self
             .ws_client()
-            .get_contents(course_id)
-            .await?
-            .into_iter()
-            .flat_map(move |section| {
-                let section_name = format!("{} {}", section.id, section.name);
-                let course_path = course_path.clone();
-                section.modules.into_iter().map(move |module| {
-                    (
-                        module,
-                        course_path.join(sanitize_path_component(&section_name).as_ref()),
-                    )
-                })
-            })
-            .filter_map(|(module, section_name)| {
-                let module_name = format!("{} {}", module.id, module.name);
-                module.contents.map(move |contents| {
-                    (
-                        section_name.join(sanitize_path_component(&module_name).as_ref()),
-                        contents,
-                    )
-                })
+            .await
+            .get_contents_batch(&course_ids)
+            .await?;
+        let contents = courses
+            .iter()
+            .zip(results)
+            .map(|((_, course_path), sections)| {
+                sections.map(|sections| self.contents(sections, course_path.clone()))
             })
-            .flat_map(|(dir, contents)| {
-                contents
-                    .into_iter()
-                    .map(move |content| Content::new(content, dir.clone()))
-            });
-
+            .collect();
         Ok(contents)
     }
+
+    fn contents(&self, sections: Vec<Section>, course_path: PathBuf) -> CourseContents {
+        let mut course = CourseCollector {
+            site_url: self.id.site_url.clone(),
+            path: course_path,
+            text_format: self.text_format,
+            url_format: self.url_format,
+            contents: Vec::new(),
+            links: Vec::new(),
+            documents: Vec::new(),
+        };
+        for section in sections {
+            course.push_section(section);
+        }
+        course.finish(self.link_index)
+    }
+
+    /// Retrieves the forum discussions including attachments and the grades
+    /// of a course, which are captured when archiving it.
+    ///
+    /// Forums and grades the site does not provide access to are skipped.
+    pub async fn get_archive_contents(
+        &self,
+        course_id: u64,
+        course_path: PathBuf,
+    ) -> ws::Result<WithWarnings<Vec<Content>>> {
+        let ws_client = self.ws_client().await;
+        let mut archive = ArchiveContents {
+            ws_client: &ws_client,
+            path: course_path,
+            text_format: self.text_format,
+            url_format: self.url_format,
+            contents: Vec::new(),
+            warnings: Vec::new(),
+        };
+
+        match ws_client.get_forums(course_id).await {
+            Ok(forums) => {
+                for forum in forums {
+                    let name = forum.name.clone();
+                    match archive.push_forum(forum).await {
+                        Err(err) if err.is_unavailable() => {
+                            warn!("Skipping forum \"{name}\": {err}");
+                        }
+                        res => res?,
+                    }
+                }
+            }
+            Err(err) if err.is_unavailable() => warn!("Skipping forums: {err}"),
+            Err(err) => return Err(err),
+        }
+
+        match ws_client.get_grade_items(course_id, self.id.user_id).await {
+            Ok(user_grades) => {
+                archive.warnings.extend(user_grades.warnings);
+                archive.push_grades(&user_grades.data);
+            }
+            Err(err) if err.is_unavailable() => warn!("Skipping grades: {err}"),
+            Err(err) => return Err(err),
+        }
+
+        Ok(WithWarnings {
+            data: archive.contents,
+            warnings: archive.warnings,
+        })
+    }
+}
+
+/// Forum discussions and grades of a course, which are only captured when
+/// archiving it.
+struct ArchiveContents<'a> {
+    ws_client: &'a ws::Client,
+    path: PathBuf,
+    text_format: TextFormat,
+    url_format: UrlFormat,
+    contents: Vec<Content>,
+    warnings: Vec<Warning>,
+}
+
+impl ArchiveContents<'_> {
+    async fn push_forum(&mut self, forum: Forum) -> ws::Result<()> {
+        let forum_name = format!("{} {}", forum.id, forum.name);
+        let forum_path = self
+            .path
+            .join("Forums")
+            .join(sanitize_path_component(&forum_name).as_ref());
+        let discussions = self.ws_client.get_discussions(forum.id).await?;
+        self.warnings.extend(discussions.warnings);
+        for discussion in discussions.data {
+            let posts = self.ws_client.get_posts(discussion.discussion).await?;
+            self.warnings.extend(posts.warnings);
+            let posts = posts.data;
+            let discussion_name = format!("{} {}", discussion.discussion, discussion.name);
+            let file_name = format!("{discussion_name}.{}", self.text_format.extension());
+            let path = forum_path.join(sanitize_path_component(&file_name).as_ref());
+            let attachments_path = forum_path
+                .join(sanitize_path_component(&format!("{discussion_name} attachments")).as_ref());
+            self.contents.push(Content::from_text(
+                discussion_html(&discussion.name, &posts),
+                SummaryFormat::Html,
+                path,
+                self.text_format,
+            ));
+            for post in posts {
+                let post_path = attachments_path.join(post.id.to_string());
+                for attachment in post.attachments {
+                    let ws_content = WsContent {
+                        ty: Type::File,
+                        name: attachment.name,
+                        path: None,
+                        size: attachment.size,
+                        url: Some(attachment.url),
+                        content: None,
+                        created: None,
+                        modified: attachment.modified,
+                        sortorder: None,
+                        media_type: attachment.media_type,
+                        external_file: None,
+                        repository_type: None,
+                        user_id: None,
+                        author: None,
+                        license: None,
+                        tags: None,
+                    };
+                    self.contents.push(Content::new(
+                        ws_content,
+                        post_path.clone(),
+                        None,
+                        self.url_format,
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn push_grades(&mut self, user_grades: &[UserGrades]) {
+        let rows = user_grades
+            .iter()
+            .flat_map(|user_grades| &user_grades.grade_items)
+            .map(grade_row)
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            return;
+        }
+        let html = format!(
+            concat!(
+                "<h1>Grades</h1>\n<table>\n<thead>\n<tr>",
+                "<th>Item</th><th>Grade</th><th>Range</th><th>Percentage</th><th>Feedback</th>",
+                "</tr>\n</thead>\n<tbody>\n{}</tbody>\n</table>\n",
+            ),
+            rows.concat()
+        );
+        let path = self
+            .path
+            .join("grades")
+            .with_extension(self.text_format.extension());
+        self.contents.push(Content::from_text(
+            html,
+            SummaryFormat::Html,
+            path,
+            self.text_format,
+        ));
+    }
+}
+
+/// Renders a message as HTML.
+fn message_html(message: &str, format: SummaryFormat) -> String {
+    match format {
+        SummaryFormat::Html | SummaryFormat::Moodle => message.to_string(),
+        SummaryFormat::Markdown | SummaryFormat::Plain => {
+            format!("<pre>{}</pre>", html_escape::encode_text(message))
+        }
+    }
+}
+
+/// Renders the posts of a discussion as one HTML document.
+fn discussion_html(name: &str, posts: &[Post]) -> String {
+    let mut html = format!("<h1>{}</h1>\n", html_escape::encode_text(name));
+    for post in posts {
+        let author = post.author.full_name.as_deref().unwrap_or("Unknown");
+        let created = post
+            .created
+            .format(format_description!(
+                "[year]-[month]-[day] [hour]:[minute] UTC"
+            ))
+            .unwrap();
+        html.push_str(&format!(
+            "<article>\n<h2>{}</h2>\n<p>{}, {created}</p>\n{}\n</article>\n",
+            html_escape::encode_text(&post.subject),
+            html_escape::encode_text(author),
+            message_html(&post.message, post.message_format),
+        ));
+    }
+    html
+}
+
+/// Renders a grade item as an HTML table row.
+fn grade_row(grade_item: &GradeItem) -> String {
+    let name = match (&grade_item.name, grade_item.ty.as_str()) {
+        (Some(name), _) => html_escape::encode_text(name).into_owned(),
+        (None, "course") => "Course total".to_string(),
+        (None, _) => String::new(),
+    };
+    // The formatted values are HTML already.
+    let cell = |value: &Option<String>| value.as_deref().unwrap_or("-").to_string();
+    let feedback = grade_item
+        .feedback
+        .as_deref()
+        .map(|feedback| {
+            message_html(
+                feedback,
+                grade_item.feedback_format.unwrap_or(SummaryFormat::Html),
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        "<tr><td>{name}</td><td>{}</td><td>{}</td><td>{}</td><td>{feedback}</td></tr>\n",
+        cell(&grade_item.grade),
+        cell(&grade_item.range),
+        cell(&grade_item.percentage),
+    )
+}
+
+/// The contents of a course.
+#[derive(Debug)]
+pub struct CourseContents {
+    pub contents: Vec<Content>,
+    /// The link index, which has to be written after syncing the contents.
+    pub link_index: Option<LinkIndex>,
+}
+
+/// Collects the contents and links of a course.
+struct CourseCollector {
+    site_url: Url,
+    path: PathBuf,
+    text_format: TextFormat,
+    url_format: UrlFormat,
+    contents: Vec<Content>,
+    links: Vec<Link>,
+    /// Exported documents whose links are read once they are synced.
+    documents: Vec<Document>,
+}
+
+impl CourseCollector {
+    fn push_links(
+        &mut self,
+        section: &str,
+        module: Option<&str>,
+        origin: &Origin,
+        links: Vec<(String, Url)>,
+    ) {
+        self.links.extend(links.into_iter().map(|(text, url)| Link {
+            section: section.to_string(),
+            module: module.map(str::to_string),
+            text,
+            url,
+            origin: origin.clone(),
+        }));
+    }
+
+    fn push_section(&mut self, section: Section) {
+        let text_format = self.text_format;
+        let section_name = format!("{} {}", section.id, section.name);
+        let section_path = self
+            .path
+            .join(sanitize_path_component(&section_name).as_ref());
+        if let Some((text, source_format)) = section_text(&section) {
+            let links = match source_format {
+                SummaryFormat::Html | SummaryFormat::Moodle => {
+                    links::from_html(&text, &self.site_url)
+                }
+                SummaryFormat::Markdown | SummaryFormat::Plain => {
+                    links::from_markdown(&text, &self.site_url)
+                }
+            };
+            self.push_links(&section.name, None, &Origin::Summary, links);
+            let path = section_path.join(text_format.index_file_name());
+            self.contents
+                .push(Content::from_text(text, source_format, path, text_format));
+        }
+        for module in section.modules {
+            self.push_module(&section.name, &section_path, module);
+        }
+    }
+
+    fn push_module(&mut self, section: &str, section_path: &Path, module: Module) {
+        let text_format = self.text_format;
+        let url_format = self.url_format;
+        let module_name = format!("{} {}", module.id, module.name);
+        let module_path = section_path.join(sanitize_path_component(&module_name).as_ref());
+        let export = content::exports_html(&module.ty).then_some(text_format);
+
+        let text = module_text(&module);
+        if let Some(text) = &text {
+            let links = links::from_html(text, &self.site_url);
+            self.push_links(section, Some(&module.name), &Origin::Description, links);
+        }
+
+        let mut contents = Vec::new();
+        for ws_content in module.contents.into_iter().flatten() {
+            let origin = Origin::Document(ws_content.name.clone());
+            let inline_links = match (ws_content.ty, &ws_content.url, &ws_content.content) {
+                (Type::Url, Some(url), _) => vec![(ws_content.name.clone(), url.clone())],
+                (Type::Content, _, Some(content)) if export.is_some() => {
+                    links::from_html(content, &self.site_url)
+                }
+                _ => Vec::new(),
+            };
+            let is_bookmark = url_format == UrlFormat::Bookmarks && ws_content.ty == Type::Url;
+            let ty = ws_content.ty;
+            let content = Content::new(ws_content, module_path.clone(), export, url_format);
+
+            if ty == Type::Url {
+                self.push_links(section, Some(&module.name), &Origin::Url, inline_links);
+            } else if content.is_export() {
+                if ty == Type::File {
+                    self.documents.push(Document {
+                        position: self.links.len(),
+                        section: section.to_string(),
+                        module: Some(module.name.clone()),
+                        origin,
+                        path: content.path().to_path_buf(),
+                    });
+                } else {
+                    self.push_links(section, Some(&module.name), &origin, inline_links);
+                }
+            }
+
+            if !is_bookmark {
+                contents.push(content);
+            }
+        }
+
+        if let Some(text) = text {
+            // Pages provide their content as `index.html` themselves.
+            let mut path = module_path.join(text_format.index_file_name());
+            if contents.iter().any(|content| content.path() == path) {
+                path = module_path
+                    .join("README")
+                    .with_extension(text_format.extension());
+            }
+            contents.push(Content::from_text(
+                text,
+                SummaryFormat::Html,
+                path,
+                text_format,
+            ));
+        }
+
+        self.contents.append(&mut contents);
+    }
+
+    /// Returns the contents including the bookmarks and the link index.
+    fn finish(mut self, link_index: bool) -> CourseContents {
+        if self.url_format == UrlFormat::Bookmarks {
+            let bookmarks = self
+                .links
+                .iter()
+                .filter(|link| link.origin == Origin::Url)
+                .cloned()
+                .collect::<Vec<_>>();
+            if !bookmarks.is_empty() {
+                let markdown = links::to_markdown(&bookmarks);
+                let path = self.path.join("links.md");
+                self.contents.push(Content::from_raw(markdown, path));
+            }
+        }
+
+        let link_index = link_index
+            .then(|| LinkIndex::new(self.path, self.site_url, self.links, self.documents));
+        CourseContents {
+            contents: self.contents,
+            link_index,
+        }
+    }
+}
+
+/// Joins the non-empty parts of a text.
+fn join_parts<'a>(
+    parts: impl IntoIterator<Item = &'a Option<String>>,
+    separator: &str,
+) -> Option<String> {
+    let parts = parts
+        .into_iter()
+        .flatten()
+        .filter(|part| !part.trim().is_empty())
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    (!parts.is_empty()).then(|| parts.join(separator))
+}
+
+/// The summary and availability information of a section.
+fn section_text(section: &Section) -> Option<(String, SummaryFormat)> {
+    let summary = Some(section.summary.clone());
+    match section.summary_format {
+        SummaryFormat::Html | SummaryFormat::Moodle => {
+            let body = join_parts([&summary, &section.availability_info], "\n")?;
+            let heading = html_escape::encode_text(&section.name);
+            Some((format!("<h1>{heading}</h1>\n{body}"), SummaryFormat::Html))
+        }
+        SummaryFormat::Markdown | SummaryFormat::Plain => {
+            let availability_info = section
+                .availability_info
+                .as_deref()
+                .and_then(|availability_info| html::to_markdown(availability_info).ok());
+            let text = join_parts([&summary, &availability_info], "\n\n")?;
+            Some((text, section.summary_format))
+        }
+    }
+}
+
+/// The description and availability information of a module.
+fn module_text(module: &Module) -> Option<String> {
+    let body = join_parts(
+        [
+            &module.description,
+            &module.availability_info,
+            &module.after_link_info,
+        ],
+        "\n",
+    )?;
+    // The name of a label is an excerpt of its description.
+    if module.ty == "label" {
+        Some(body)
+    } else {
+        let heading = html_escape::encode_text(&module.name);
+        Some(format!("<h1>{heading}</h1>\n{body}"))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SsoError {
+    #[error("could not retrieve the public site configuration")]
+    ConfigError(#[from] ajax::Error),
+    #[error("the site does not support SSO for the mobile app")]
+    NoLaunchUrl,
+    #[error(transparent)]
+    ValidationError(#[from] sso::Error),
+    #[error("could not retrieve the site info")]
+    InfoError(#[from] ws::RequestError),
 }
 
 pub struct Builder {
+    http: reqwest::Client,
     site_url: Url,
     lang: Option<String>,
     token_builder: SSOTokenBuilder,
 }
 
 impl Builder {
-    pub async fn new(site_url: Url, url_scheme: &str, lang: Option<String>) -> (Url, Self) {
-        let ajax_client = ajax::Client::new(util::shared_http(), &site_url);
-        let site_config = ajax_client.get_config().await.unwrap();
+    /// Prepares the SSO handshake and returns the URL to open in a browser.
+    ///
+    /// After logging in, the site redirects to `{url_scheme}://token=...`.
+    pub async fn new(
+        http: reqwest::Client,
+        site_url: Url,
+        url_scheme: &str,
+        lang: Option<String>,
+    ) -> Result<(Url, Self), SsoError> {
+        let site_config = Account::public_config(http.clone(), &site_url).await?;
+        let launch_url = site_config.launch_url.ok_or(SsoError::NoLaunchUrl)?;
+        Ok(Self::with_launch_url(
+            http, site_url, launch_url, url_scheme, lang,
+        ))
+    }
+
+    /// Like [`Builder::new`], but with an already known launch URL.
+    #[must_use]
+    pub fn with_launch_url(
+        http: reqwest::Client,
+        site_url: Url,
+        launch_url: Url,
+        url_scheme: &str,
+        lang: Option<String>,
+    ) -> (Url, Self) {
         let (sso_url, token_builder) =
-            SSOTokenBuilder::prepare_sso(&site_url, site_config.launch_url.unwrap(), url_scheme);
+            SSOTokenBuilder::prepare_sso(&site_url, launch_url, url_scheme);
         (
             sso_url,
             Self {
+                http,
                 site_url,
                 lang,
                 token_builder,
@@ -139,22 +763,60 @@ impl Builder {
         )
     }
 
-    pub async fn validate(self, token_url: &Url) -> Result<Account, sso::Error> {
+    pub async fn validate(self, token_url: &Url) -> Result<Account, SsoError> {
         let token = self.token_builder.validate(token_url)?;
-        let ws_client = ws::Client::new(
-            util::shared_http(),
-            &self.site_url,
-            token,
-            self.lang.clone(),
-        );
+        let ws_client =
+            util::ws_client(self.http.clone(), &self.site_url, token, self.lang.clone());
         let Info {
             site_url, user_id, ..
-        } = ws_client.get_info().await.unwrap();
+        } = ws_client.get_info().await?;
         let id = Id {
             site_url,
             user_id,
             lang: self.lang,
         };
-        Ok(Account::new(id, token))
+        Ok(Account::new(id, token).with_http(self.http))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(summary: &str, summary_format: SummaryFormat) -> Section {
+        Section {
+            id: 1,
+            name: "Week <1>".to_string(),
+            visible: None,
+            summary: summary.to_string(),
+            summary_format,
+            section: Some(1),
+            hidden_by_num_sections: None,
+            user_visible: None,
+            availability_info: None,
+            modules: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn section_text_test() {
+        assert_eq!(section_text(&section(" ", SummaryFormat::Html)), None);
+        assert_eq!(
+            section_text(&section("<p>Summary</p>", SummaryFormat::Html)),
+            Some((
+                "<h1>Week &lt;1&gt;</h1>\n<p>Summary</p>".to_string(),
+                SummaryFormat::Html
+            ))
+        );
+
+        let mut section = section("*Summary*", SummaryFormat::Markdown);
+        section.availability_info = Some("<strong>Not available</strong>".to_string());
+        assert_eq!(
+            section_text(&section),
+            Some((
+                "*Summary*\n\n**Not available**".to_string(),
+                SummaryFormat::Markdown
+            ))
+        );
     }
 }
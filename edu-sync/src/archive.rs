@@ -0,0 +1,273 @@
+//! Archives of finished courses.
+//!
+//! File operations block.
+
+use std::{
+    fmt::{self, Display},
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use time::OffsetDateTime;
+use url::Url;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{account::Id, util::PathBufExt};
+
+/// The file name of the manifest in the course directory.
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// A list of everything captured when archiving a course.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct Manifest {
+    pub course_id: u64,
+    pub name: String,
+    pub account: String,
+    pub site_url: Url,
+    #[serde(with = "time::serde::rfc3339")]
+    pub archived: OffsetDateTime,
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct ManifestFile {
+    /// The path relative to the course directory, separated by `/`.
+    pub path: String,
+    pub size: u64,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub modified: Option<OffsetDateTime>,
+}
+
+impl Manifest {
+    /// Lists the files in `course_path`.
+    pub fn new(course_id: u64, name: String, id: &Id, course_path: &Path) -> io::Result<Self> {
+        let files = relative_files(course_path)?
+            .into_iter()
+            .map(|path| {
+                let metadata = fs::metadata(course_path.join(&path))?;
+                Ok(ManifestFile {
+                    path: slash_path(&path),
+                    size: metadata.len(),
+                    modified: metadata.modified().ok().map(OffsetDateTime::from),
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Self {
+            course_id,
+            name,
+            account: id.to_string(),
+            site_url: id.site_url.clone(),
+            archived: OffsetDateTime::now_utc(),
+            files,
+        })
+    }
+
+    /// Writes the manifest to the root of `course_path`.
+    pub fn write(&self, course_path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).unwrap();
+        fs::write(course_path.join(MANIFEST_FILE_NAME), json)
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("unknown archive format {0:?}, expected tar.gz or zip")]
+pub struct ParseFormatError(String);
+
+/// The format to pack an archived course into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    TarGz,
+    Zip,
+}
+
+impl Format {
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::TarGz => "tar.gz",
+            Self::Zip => "zip",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tar.gz" | "tgz" => Ok(Self::TarGz),
+            "zip" => Ok(Self::Zip),
+            s => Err(ParseFormatError(s.to_string())),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// Packs the course directory into a file next to it, with the manifest at
+/// the root.
+///
+/// Returns the path of the packed file.
+pub fn pack(course_path: &Path, format: Format) -> io::Result<PathBuf> {
+    let mut files = relative_files(course_path)?;
+    // The manifest comes first, so that it can be read without unpacking
+    // everything.
+    let manifest = PathBuf::from(MANIFEST_FILE_NAME);
+    if let Some(index) = files.iter().position(|path| *path == manifest) {
+        files.remove(index);
+        files.insert(0, manifest);
+    }
+
+    let mut dst_path = course_path.to_path_buf();
+    dst_path.push_file_name_suffix(format!(".{}", format.extension()));
+    let mut tmp_path = dst_path.clone();
+    tmp_path.push_file_name_suffix(".tmp");
+    let file = BufWriter::new(File::create(&tmp_path)?);
+
+    match format {
+        Format::TarGz => {
+            let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+            for path in &files {
+                builder.append_path_with_name(course_path.join(path), path)?;
+            }
+            builder.into_inner()?.finish()?;
+        }
+        Format::Zip => {
+            let mut zip = ZipWriter::new(file);
+            for path in &files {
+                let src_path = course_path.join(path);
+                let mut options =
+                    SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+                let modified = fs::metadata(&src_path)?.modified()?;
+                if let Ok(modified) = OffsetDateTime::from(modified).try_into() {
+                    options = options.last_modified_time(modified);
+                }
+                zip.start_file(slash_path(path), options)
+                    .map_err(io::Error::other)?;
+                io::copy(&mut File::open(src_path)?, &mut zip)?;
+            }
+            zip.finish().map_err(io::Error::other)?;
+        }
+    }
+
+    fs::rename(tmp_path, &dst_path)?;
+    Ok(dst_path)
+}
+
+/// Lists the files in `dir` recursively, relative to it and sorted.
+///
+/// Unfinished downloads are skipped.
+fn relative_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    fn visit(dir: &Path, base: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                visit(&path, base, files)?;
+            } else if file_type.is_file()
+                && path.extension().is_none_or(|extension| extension != "tmp")
+            {
+                files.push(path.strip_prefix(base).unwrap().to_path_buf());
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    visit(dir, dir, &mut files)?;
+    files.sort_unstable();
+    Ok(files)
+}
+
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    #[test]
+    fn format_test() {
+        assert_eq!("tar.gz".parse::<Format>().unwrap(), Format::TarGz);
+        assert_eq!("tgz".parse::<Format>().unwrap(), Format::TarGz);
+        assert_eq!("zip".parse::<Format>().unwrap(), Format::Zip);
+        assert!("rar".parse::<Format>().is_err());
+        assert_eq!(Format::TarGz.to_string(), "tar.gz");
+    }
+
+    #[test]
+    fn pack_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let course_path = dir.path().join("42 Linear Algebra");
+        fs::create_dir_all(course_path.join("1 Section")).unwrap();
+        fs::write(course_path.join("1 Section/notes.pdf"), "notes").unwrap();
+        fs::write(course_path.join("1 Section/notes.pdf.tmp"), "partial").unwrap();
+        fs::write(course_path.join("grades.html"), "grades").unwrap();
+
+        let id = Id {
+            site_url: "https://example.com/".parse().unwrap(),
+            user_id: 1,
+            lang: None,
+        };
+        let manifest =
+            Manifest::new(42, "42 Linear Algebra".to_string(), &id, &course_path).unwrap();
+        assert_eq!(
+            manifest
+                .files
+                .iter()
+                .map(|file| (file.path.as_str(), file.size))
+                .collect::<Vec<_>>(),
+            [("1 Section/notes.pdf", 5), ("grades.html", 6)]
+        );
+        assert_eq!(manifest.account, "1@example.com");
+        manifest.write(&course_path).unwrap();
+
+        let tar_path = pack(&course_path, Format::TarGz).unwrap();
+        assert_eq!(tar_path, dir.path().join("42 Linear Algebra.tar.gz"));
+        let mut tar = tar::Archive::new(GzDecoder::new(File::open(&tar_path).unwrap()));
+        let paths = tar
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                Path::new(MANIFEST_FILE_NAME),
+                Path::new("1 Section/notes.pdf"),
+                Path::new("grades.html")
+            ]
+        );
+
+        let zip_path = pack(&course_path, Format::Zip).unwrap();
+        let mut zip = zip::ZipArchive::new(File::open(&zip_path).unwrap()).unwrap();
+        assert_eq!(zip.len(), 3);
+        let mut notes = String::new();
+        zip.by_name("1 Section/notes.pdf")
+            .unwrap()
+            .read_to_string(&mut notes)
+            .unwrap();
+        assert_eq!(notes, "notes");
+        assert_eq!(zip.by_index(0).unwrap().name(), MANIFEST_FILE_NAME);
+    }
+}
@@ -5,25 +5,36 @@ use std::{
     fmt::{self, Display},
     io::{self, ErrorKind},
     path::{Path, PathBuf},
+    str::FromStr,
     sync::OnceLock,
 };
 
 use edu_ws::{
+    capabilities::SiteCapabilities,
     response::{course::Course, info::Info},
     token::Token,
     ws,
 };
+use globset::{Glob, GlobBuilder, GlobMatcher};
 use reqwest::Url;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_with::{serde_as, serde_conv, DisplayFromStr};
 use thiserror::Error;
+use time::OffsetDateTime;
 use tokio::{
     fs::{self, File},
     io::AsyncWriteExt,
 };
-use tracing::warn;
+use tracing::{info, warn};
 
-use crate::{account::Id, util};
+use crate::{
+    account::Id,
+    content::{TextFormat, UrlFormat},
+    http::HttpConfig,
+    rules::{CategoryPath, Rules},
+    secret::{self, SecretRef, TokenRef},
+    util,
+};
 
 #[derive(Error, Debug)]
 pub enum TomlReadError {
@@ -33,32 +44,170 @@ pub enum TomlReadError {
     TomlError(#[from] toml::de::Error),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub struct CourseConfig {
     pub name: String,
     pub sync: bool,
+    /// A name chosen by the user for the course directory.
+    ///
+    /// Unlike the name, the alias is kept when the course is renamed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub short_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_name: Option<String>,
+    /// The id of the course category.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<u64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    pub start_date: Option<OffsetDateTime>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    pub end_date: Option<OffsetDateTime>,
+    /// Whether the course was archived, in which case it is no longer synced.
+    #[serde(default, skip_serializing_if = "util::is_false")]
+    pub archived: bool,
 }
 
 impl CourseConfig {
+    /// The name of the course directory, which is the alias if set.
     #[must_use]
     pub fn name_as_path_component(&self) -> Cow<'_, str> {
-        util::sanitize_path_component(&self.name)
+        util::sanitize_path_component(self.alias.as_ref().unwrap_or(&self.name))
+    }
+}
+
+/// A case-insensitive glob pattern, such as `*Algebra*`.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    glob: Glob,
+    matcher: GlobMatcher,
+}
+
+impl Pattern {
+    #[must_use]
+    pub fn is_match(&self, text: &str) -> bool {
+        self.matcher.is_match(text)
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = globset::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let glob = GlobBuilder::new(s)
+            .case_insensitive(true)
+            .literal_separator(false)
+            .build()?;
+        let matcher = glob.compile_matcher();
+        Ok(Self { glob, matcher })
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.glob.glob())
+    }
+}
+
+/// Selects courses by id or by a pattern matching their name.
+#[derive(Debug, Clone)]
+pub enum CourseSelector {
+    Id(u64),
+    Name(Pattern),
+}
+
+impl FromStr for CourseSelector {
+    type Err = globset::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(id) => Ok(Self::Id(id)),
+            Err(_) => s.parse().map(Self::Name),
+        }
+    }
+}
+
+impl Display for CourseSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Id(id) => write!(f, "{id}"),
+            Self::Name(pattern) => write!(f, "\"{pattern}\""),
+        }
+    }
+}
+
+impl CourseSelector {
+    /// Whether the course matches.
+    ///
+    /// Names are matched with and without the leading course id and against
+    /// the alias.
+    #[must_use]
+    pub fn matches(&self, id: u64, course_config: &CourseConfig) -> bool {
+        match self {
+            Self::Id(selected_id) => *selected_id == id,
+            Self::Name(pattern) => {
+                let full_name = course_config
+                    .name
+                    .strip_prefix(&format!("{id} "))
+                    .unwrap_or(&course_config.name);
+                pattern.is_match(&course_config.name)
+                    || pattern.is_match(full_name)
+                    || course_config
+                        .alias
+                        .as_ref()
+                        .is_some_and(|alias| pattern.is_match(alias))
+            }
+        }
     }
 }
 
 impl From<Course> for CourseConfig {
     fn from(course: Course) -> Self {
+        // Moodle uses 0 for unset dates.
+        let date = |date: Option<OffsetDateTime>| date.filter(|date| date.unix_timestamp() > 0);
         Self {
             name: format!("{} {}", course.id, course.full_name),
             sync: false,
+            alias: None,
+            short_name: Some(course.short_name),
+            full_name: Some(course.full_name),
+            category: course.category,
+            start_date: date(course.start_date),
+            end_date: date(course.end_date),
+            archived: false,
         }
     }
 }
 
 impl CourseConfig {
+    /// Keeps the settings of the user from `other`.
     fn apply(&mut self, other: &Self) {
         self.sync = other.sync;
+        self.alias.clone_from(&other.alias);
+        self.archived = other.archived;
+        if self.name != other.name && other.sync {
+            match &self.alias {
+                Some(alias) => warn!(
+                    "Course \"{}\" was renamed to \"{}\". Its directory stays \"{alias}\" \
+                     due to its alias.",
+                    other.name, self.name
+                ),
+                None => warn!(
+                    "Course \"{}\" was renamed to \"{}\". To keep its directory, set its alias.",
+                    other.name, self.name
+                ),
+            }
+        }
     }
 }
 
@@ -89,20 +238,69 @@ serde_conv!(
 pub struct CourseConfigs(#[serde_as(as = "CourseConfigsSorter")] pub BTreeMap<u64, CourseConfig>);
 
 impl CourseConfigs {
-    pub fn update(&mut self, courses: Vec<Course>) {
-        let mut new_configs = courses
-            .into_iter()
-            .map(|course| (course.id, CourseConfig::from(course)))
-            .collect::<BTreeMap<_, _>>();
-        for (id, config) in &self.0 {
-            if let Some(new_config) = new_configs.get_mut(id) {
-                new_config.apply(config);
+    /// Replaces the courses with the available `courses`, keeping their
+    /// configuration and applying `rules`.
+    pub fn update(
+        &mut self,
+        courses: Vec<Course>,
+        rules: &Rules,
+        categories: &BTreeMap<u64, CategoryPath>,
+    ) {
+        let now = OffsetDateTime::now_utc();
+        let mut new_configs = BTreeMap::new();
+        for course in courses {
+            let id = course.id;
+            let old_config = self.0.get(&id);
+            let category = course
+                .category
+                .and_then(|category| categories.get(&category));
+            let sync = rules.sync(&course, category, old_config.is_none(), now);
+            let mut new_config = CourseConfig::from(course);
+            if let Some(old_config) = old_config {
+                new_config.apply(old_config);
+            }
+            if let Some(sync) = sync {
+                if new_config.sync != sync {
+                    let action = if sync { "Enabled" } else { "Disabled" };
+                    info!("{action} course \"{}\" ({}) by rule", new_config.name, id);
+                    new_config.sync = sync;
+                }
+            }
+            new_configs.insert(id, new_config);
+        }
+        for (&id, config) in &self.0 {
+            if new_configs.contains_key(&id) {
+                continue;
+            }
+            if config.archived {
+                // Archived courses are kept, as access often ends with the course.
+                new_configs.insert(id, config.clone());
             } else {
                 warn!("Course \"{}\" ({}) is unavailable", config.name, id);
             }
         }
         self.0 = new_configs;
     }
+
+    /// Finds a course other than `id` that `alias` would be confused with,
+    /// because its name, alias or directory equals `alias` ignoring case.
+    #[must_use]
+    pub fn alias_conflict(&self, id: u64, alias: &str) -> Option<(u64, &CourseConfig)> {
+        let alias = alias.to_lowercase();
+        let path_component = util::sanitize_path_component(&alias);
+        self.0
+            .iter()
+            .find(|&(&other_id, other)| {
+                other_id != id
+                    && (other.name.to_lowercase() == alias
+                        || other
+                            .alias
+                            .as_ref()
+                            .is_some_and(|other_alias| other_alias.to_lowercase() == alias)
+                        || other.name_as_path_component().to_lowercase() == path_component)
+            })
+            .map(|(&other_id, other)| (other_id, other))
+    }
 }
 
 // Expand Tilde to home folder, create the directory if it does not exist and
@@ -110,7 +308,7 @@ impl CourseConfigs {
 pub fn expand_path(path: &Path) -> io::Result<PathBuf> {
     let expanded_path = shellexpand::path::tilde(&path);
     if !expanded_path.try_exists()? {
-        std::fs::create_dir(&expanded_path)?;
+        std::fs::create_dir_all(&expanded_path)?;
     }
     std::fs::canonicalize(expanded_path)
 }
@@ -125,16 +323,47 @@ where
     expand_path(&path).map_err(serde::de::Error::custom)
 }
 
+const fn default_link_index() -> bool {
+    true
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "kebab-case")]
 pub struct AccountConfig {
     pub user: String,
+    /// The login name, used for renewing the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
     pub site: String,
     #[serde(flatten)]
     pub id: Id,
-    pub token: Token,
+    /// The token or, if stored in another backend, a reference to it.
+    pub token: TokenRef,
+    /// The private token returned on login, stored like the token.
+    ///
+    /// Moodle requires it for requesting autologin keys.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_token: Option<SecretRef<String>>,
     #[serde(deserialize_with = "deserialize_absolute_path")]
     pub path: PathBuf,
+    /// The format to export page, book and label content in.
+    #[serde(default)]
+    pub text_format: TextFormat,
+    /// The format to write URL modules in.
+    #[serde(default)]
+    pub url_format: UrlFormat,
+    /// Whether to write an index of all external links per course.
+    #[serde(default = "default_link_index")]
+    pub link_index: bool,
+    /// Rules for enabling and disabling courses on fetch.
+    #[serde(default, skip_serializing_if = "Rules::is_empty")]
+    pub rules: Rules,
+    /// How to connect to the site.
+    #[serde(default, skip_serializing_if = "HttpConfig::is_default")]
+    pub http: HttpConfig,
+    /// The capabilities of the site as of the last fetch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<SiteCapabilities>,
     #[serde(default)]
     pub courses: CourseConfigs,
 }
@@ -146,23 +375,48 @@ impl Display for AccountConfig {
 }
 
 impl AccountConfig {
+    /// Replaces the token and private token, storing them in `store`.
+    pub fn set_tokens(
+        &mut self,
+        store: &mut secret::Store,
+        token: Token,
+        private_token: Option<String>,
+    ) -> Result<(), secret::Error> {
+        let name = self.id.to_string();
+        store.remove(&self.token)?;
+        self.token = store.insert(&name, token)?;
+        if let Some(old_private_token) = self.private_token.take() {
+            store.remove(&old_private_token)?;
+        }
+        if let Some(private_token) = private_token {
+            let name = format!("{name}/private-token");
+            self.private_token = Some(store.insert(&name, private_token)?);
+        }
+        store.flush()
+    }
+
+    /// Creates the configuration of the account with `token`.
+    ///
+    /// The site info is requested via `http`, which should match
+    /// [`AccountConfig::http`].
     pub async fn new(
+        http: reqwest::Client,
         site_url: Url,
         token: Token,
         path: PathBuf,
         lang: Option<String>,
-    ) -> Result<Self, ws::Error> {
-        let ws_client = ws::Client::new(util::shared_http(), &site_url, token, lang.clone());
+    ) -> ws::Result<Self> {
+        let ws_client = util::ws_client(http, &site_url, token, lang.clone());
+        let info = ws_client.get_info().await?;
+        let capabilities = SiteCapabilities::from(&info);
         let Info {
             site_url,
             user_id,
+            username,
             full_name,
             site_name,
             ..
-        } = ws_client.get_info().await.map_err(|err| match err {
-            ws::RequestError::WsError(err) => err,
-            err => panic!("{err:?}"),
-        })?;
+        } = info;
         let id = Id {
             site_url,
             user_id,
@@ -170,10 +424,18 @@ impl AccountConfig {
         };
         Ok(Self {
             user: full_name,
+            username: Some(username),
             site: site_name,
             id,
-            token,
+            token: TokenRef::Plaintext(token),
+            private_token: None,
             path,
+            text_format: TextFormat::default(),
+            url_format: UrlFormat::default(),
+            link_index: default_link_index(),
+            rules: Rules::default(),
+            http: HttpConfig::default(),
+            capabilities: Some(capabilities),
             courses: CourseConfigs(BTreeMap::new()),
         })
     }
@@ -183,6 +445,9 @@ impl AccountConfig {
 #[serde(rename_all = "kebab-case")]
 pub struct Config {
     pub parallel_downloads: usize,
+    /// Where to store tokens.
+    #[serde(default)]
+    pub secret_backend: secret::Backend,
     #[serde(default)]
     pub accounts: BTreeMap<String, AccountConfig>,
 }
@@ -216,7 +481,39 @@ impl Config {
         self.accounts
             .values()
             .flat_map(|account_config| account_config.courses.0.values())
-            .any(|course_config| course_config.sync)
+            .any(|course_config| course_config.sync && !course_config.archived)
+    }
+
+    /// Whether accessing the tokens requires the passphrase of the encrypted
+    /// token file.
+    pub fn needs_passphrase(&self) -> bool {
+        self.secret_backend == secret::Backend::EncryptedFile
+            || self.accounts.values().any(|account_config| {
+                account_config.token.backend() == secret::Backend::EncryptedFile
+            })
+    }
+
+    /// Copies all tokens into the backend of `store` and writes the store.
+    ///
+    /// Returns the references to the previous copies. If there are any, the
+    /// config has to be written before deleting them with
+    /// [`Store::remove_all`](secret::Store::remove_all), so that a failure
+    /// never loses the only copy of a token.
+    pub fn store_tokens(
+        &mut self,
+        store: &mut secret::Store,
+    ) -> Result<Vec<SecretRef<()>>, secret::Error> {
+        let mut old_secret_refs = Vec::new();
+        for account_config in self.accounts.values_mut() {
+            let name = account_config.id.to_string();
+            old_secret_refs.extend(store.migrate(&name, &mut account_config.token)?);
+            if let Some(private_token) = &mut account_config.private_token {
+                let name = format!("{name}/private-token");
+                old_secret_refs.extend(store.migrate(&name, private_token)?);
+            }
+        }
+        store.flush()?;
+        Ok(old_secret_refs)
     }
 
     pub async fn read() -> Result<Self, TomlReadError> {
@@ -242,7 +539,156 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             parallel_downloads: 5,
+            secret_backend: secret::Backend::default(),
             accounts: BTreeMap::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn course_selector_test() {
+        let course_config = CourseConfig {
+            name: "42 Linear Algebra".to_string(),
+            alias: Some("LA".to_string()),
+            ..CourseConfig::default()
+        };
+        let matches = |selector: &str| {
+            selector
+                .parse::<CourseSelector>()
+                .unwrap()
+                .matches(42, &course_config)
+        };
+        assert!(matches("42"));
+        assert!(!matches("4"));
+        assert!(matches("linear algebra"));
+        assert!(matches("42 Linear Algebra"));
+        assert!(matches("*algebra"));
+        assert!(!matches("Algebra"));
+        assert!(matches("Linear*"));
+        assert!(matches("la"));
+    }
+
+    #[test]
+    fn course_configs_update_test() {
+        let course = |full_name: &str| {
+            serde_json::from_value::<Course>(serde_json::json!({
+                "id": 42,
+                "shortname": "LA",
+                "fullname": full_name,
+                "idnumber": "",
+                "visible": 1,
+                "category": 4,
+                "startdate": 1_728_864_000,
+                "enddate": 0,
+            }))
+            .unwrap()
+        };
+        let mut course_configs = CourseConfigs::default();
+        course_configs.update(
+            vec![course("Linear Algebra")],
+            &Rules::default(),
+            &BTreeMap::new(),
+        );
+        let course_config = course_configs.0.get_mut(&42).unwrap();
+        assert_eq!(course_config.name_as_path_component(), "42 Linear Algebra");
+        assert_eq!(course_config.short_name.as_deref(), Some("LA"));
+        assert_eq!(course_config.category, Some(4));
+        assert_eq!(
+            course_config.start_date.map(OffsetDateTime::unix_timestamp),
+            Some(1_728_864_000)
+        );
+        assert_eq!(course_config.end_date, None);
+
+        course_config.sync = true;
+        course_config.alias = Some("Linear Algebra".to_string());
+        course_configs.update(
+            vec![course("Linear Algebra I")],
+            &Rules::default(),
+            &BTreeMap::new(),
+        );
+        let course_config = &course_configs.0[&42];
+        assert_eq!(course_config.name, "42 Linear Algebra I");
+        assert_eq!(course_config.name_as_path_component(), "Linear Algebra");
+        assert!(course_config.sync);
+
+        let toml = toml::to_string(&course_configs).unwrap();
+        let mut course_configs = toml::from_str::<CourseConfigs>(&toml).unwrap();
+        assert_eq!(course_configs.0[&42].start_date, course_config.start_date);
+
+        course_configs.0.get_mut(&42).unwrap().archived = true;
+        course_configs.update(Vec::new(), &Rules::default(), &BTreeMap::new());
+        assert!(course_configs.0[&42].archived);
+        course_configs.0.get_mut(&42).unwrap().archived = false;
+        course_configs.update(Vec::new(), &Rules::default(), &BTreeMap::new());
+        assert!(course_configs.0.is_empty());
+    }
+
+    #[test]
+    fn alias_conflict_test() {
+        let course_configs = CourseConfigs(BTreeMap::from([
+            (
+                42,
+                CourseConfig {
+                    name: "42 Linear Algebra".to_string(),
+                    alias: Some("LA".to_string()),
+                    ..CourseConfig::default()
+                },
+            ),
+            (
+                7,
+                CourseConfig {
+                    name: "7 Analysis: Part 1".to_string(),
+                    ..CourseConfig::default()
+                },
+            ),
+        ]));
+        let conflict = |id, alias| course_configs.alias_conflict(id, alias).map(|(id, _)| id);
+        assert_eq!(conflict(7, "la"), Some(42));
+        assert_eq!(conflict(7, "42 linear algebra"), Some(42));
+        assert_eq!(conflict(42, "7 Analysis_ Part 1"), Some(7));
+        assert_eq!(conflict(42, "7 Analysis: Part 1"), Some(7));
+        assert_eq!(conflict(42, "la"), None);
+        assert_eq!(conflict(42, "Linear Algebra"), None);
+    }
+
+    #[test]
+    fn capabilities_test() {
+        let mut config = toml::from_str::<Config>(&format!(
+            r#"
+            parallel-downloads = 5
+
+            [accounts."1@example.com"]
+            user = "Sam Student"
+            site = "Mock Moodle"
+            site-url = "https://example.com/"
+            user-id = 1
+            token = "ffffffffffffffffffffffffffffffff"
+            path = {:?}
+            "#,
+            std::env::temp_dir()
+        ))
+        .unwrap();
+        let account_config = config.accounts.get_mut("1@example.com").unwrap();
+        assert_eq!(account_config.capabilities, None);
+
+        let capabilities = SiteCapabilities {
+            functions: ["core_course_get_contents".to_string()].into(),
+            release: Some("4.1.2 (Build: 20230313)".to_string()),
+            version: Some(2_022_112_802),
+            advanced_features: [("enablemobilewebservice".to_string(), true)].into(),
+            can_download_files: Some(true),
+        };
+        account_config.capabilities = Some(capabilities.clone());
+
+        let toml = toml::to_string_pretty(&config).unwrap();
+        let config = toml::from_str::<Config>(&toml).unwrap();
+        assert_eq!(
+            config.accounts["1@example.com"].capabilities,
+            Some(capabilities)
+        );
+    }
+}
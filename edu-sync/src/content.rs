@@ -6,24 +6,125 @@ use std::{
 };
 
 use edu_ws::{
-    response::content::{Content as WsContent, Type},
+    response::{
+        content::{Content as WsContent, Type},
+        SummaryFormat,
+    },
     token::Token,
 };
 use reqwest::Url;
+use serde::{Deserialize, Serialize};
 use tokio::{
     fs::{self, File},
     io::{self, AsyncBufReadExt, AsyncSeekExt, AsyncWriteExt, BufReader},
     task,
 };
 
-use crate::util::{self, sanitize_path_component, PathBufExt};
+use crate::{
+    html,
+    util::{sanitize_path_component, PathBufExt},
+};
+
+/// Module types whose HTML content is exported for offline use.
+const HTML_MODULES: [&str; 3] = ["page", "book", "label"];
+
+/// Whether HTML content of modules of this type is exported for offline use.
+#[must_use]
+pub fn exports_html(module_type: &str) -> bool {
+    HTML_MODULES.contains(&module_type)
+}
+
+/// The format exported HTML content is written in.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextFormat {
+    /// HTML with rewritten resource links.
+    #[default]
+    Html,
+    /// Markdown converted from HTML, referencing the local resources.
+    Markdown,
+}
+
+impl TextFormat {
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Markdown => "md",
+        }
+    }
+
+    /// The file name for the text describing a directory.
+    #[must_use]
+    pub const fn index_file_name(self) -> &'static str {
+        match self {
+            Self::Html => "index.html",
+            Self::Markdown => "README.md",
+        }
+    }
+}
+
+/// The format URL modules are written in.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum UrlFormat {
+    /// HTML files redirecting to the URL.
+    #[default]
+    Html,
+    /// Windows Internet shortcuts (`.url`).
+    InternetShortcut,
+    /// Freedesktop link files (`.desktop`).
+    Desktop,
+    /// macOS Web locations (`.webloc`).
+    Webloc,
+    /// One `links.md` per course instead of one file per URL.
+    Bookmarks,
+}
+
+impl UrlFormat {
+    /// The suffix appended to the name of URL files.
+    ///
+    /// Bookmarks are aggregated per course by the account, single URLs fall
+    /// back to HTML.
+    #[must_use]
+    pub const fn suffix(self) -> &'static str {
+        match self {
+            Self::Html | Self::Bookmarks => ".html",
+            Self::InternetShortcut => ".url",
+            Self::Desktop => ".desktop",
+            Self::Webloc => ".webloc",
+        }
+    }
+}
+
+fn is_html(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .is_some_and(|extension| extension == "html" || extension == "htm")
+}
 
 #[derive(Debug, Clone)]
 pub struct Content {
-    ws_content: WsContent,
+    source: Source,
     path: PathBuf,
 }
 
+#[derive(Debug, Clone)]
+enum Source {
+    Ws {
+        ws_content: Box<WsContent>,
+        export: Option<TextFormat>,
+        url_format: UrlFormat,
+    },
+    /// Text without a modification time, such as section summaries.
+    Text {
+        text: String,
+        source_format: SummaryFormat,
+        /// The export format, or `None` to write the text as it is.
+        format: Option<TextFormat>,
+    },
+}
+
 #[derive(Debug)]
 pub enum SyncStatus {
     Downloadable(Download),
@@ -32,8 +133,18 @@ pub enum SyncStatus {
 }
 
 impl Content {
+    /// Creates content of a module.
+    ///
+    /// If `export` is set, HTML documents are exported in this format for
+    /// offline use. URLs are written in `url_format`.
     #[must_use]
-    pub fn new(ws_content: WsContent, module_path: PathBuf) -> Self {
+    pub fn new(
+        ws_content: WsContent,
+        module_path: PathBuf,
+        export: Option<TextFormat>,
+        url_format: UrlFormat,
+    ) -> Self {
+        let export = export.filter(|_| is_html(&ws_content.name));
         let path = {
             let mut path = module_path;
 
@@ -53,7 +164,11 @@ impl Content {
             }
 
             if ws_content.ty == Type::Url {
-                path.push_file_name_suffix(".html");
+                path.push_file_name_suffix(url_format.suffix());
+            }
+
+            if let Some(format) = export {
+                path.set_extension(format.extension());
             }
 
             let file_name = path.file_name().unwrap().to_str().unwrap();
@@ -64,41 +179,164 @@ impl Content {
             path
         };
 
-        Self { ws_content, path }
+        let source = Source::Ws {
+            ws_content: Box::new(ws_content),
+            export,
+            url_format,
+        };
+        Self { source, path }
     }
 
-    fn mtime(&self) -> SystemTime {
-        self.ws_content.modified.into()
+    /// Creates content from text that is exported to `path` in `format` and
+    /// only rewritten when it changes.
+    #[must_use]
+    pub fn from_text(
+        text: String,
+        source_format: SummaryFormat,
+        path: PathBuf,
+        format: TextFormat,
+    ) -> Self {
+        let source = Source::Text {
+            text,
+            source_format,
+            format: Some(format),
+        };
+        Self { source, path }
+    }
+
+    /// Creates content from text that is written to `path` as it is and only
+    /// rewritten when it changes.
+    #[must_use]
+    pub fn from_raw(text: String, path: PathBuf) -> Self {
+        let source = Source::Text {
+            text,
+            source_format: SummaryFormat::Plain,
+            format: None,
+        };
+        Self { source, path }
+    }
+
+    /// Whether this content is an HTML document exported for offline use.
+    #[must_use]
+    pub fn is_export(&self) -> bool {
+        matches!(
+            self.source,
+            Source::Ws {
+                export: Some(_),
+                ..
+            }
+        )
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
     }
 
-    fn download(self) -> SyncStatus {
-        let mtime = self.mtime();
-        match self.ws_content.ty {
-            Type::File => {
-                let common = CommonDownload::new(self.path, mtime);
-                let url = self.ws_content.url.unwrap();
-                let size = self.ws_content.size;
+    fn download(
+        ws_content: WsContent,
+        export: Option<TextFormat>,
+        url_format: UrlFormat,
+        path: PathBuf,
+    ) -> SyncStatus {
+        let mtime = ws_content.modified.into();
+        match (ws_content.ty, export) {
+            (Type::File, Some(format)) => {
+                let common = CommonDownload::new(path, mtime);
+                let source = HtmlSource::Url(ws_content.url.unwrap());
+                let size = ws_content.size;
+                SyncStatus::Downloadable(Download::Html(HtmlDownload {
+                    source,
+                    format: Some(format),
+                    size,
+                    common,
+                }))
+            }
+            (Type::File, None) => {
+                let common = CommonDownload::new(path, mtime);
+                let url = ws_content.url.unwrap();
+                let size = ws_content.size;
                 SyncStatus::Downloadable(Download::File(FileDownload { url, size, common }))
             }
-            Type::Url => {
-                let common = CommonDownload::new(self.path, mtime);
-                let url = self.ws_content.url.unwrap();
-                SyncStatus::Downloadable(Download::Url(UrlDownload { url, common }))
+            (Type::Url, _) => {
+                let common = CommonDownload::new(path, mtime);
+                let url = ws_content.url.unwrap();
+                let name = ws_content.name;
+                SyncStatus::Downloadable(Download::Url(UrlDownload {
+                    url,
+                    name,
+                    format: url_format,
+                    common,
+                }))
+            }
+            (Type::Content, Some(format)) => {
+                let common = CommonDownload::new(path, mtime);
+                let content = ws_content.content.unwrap();
+                let size = content.len() as u64;
+                let source = HtmlSource::Inline(content, SummaryFormat::Html);
+                SyncStatus::Downloadable(Download::Html(HtmlDownload {
+                    source,
+                    format: Some(format),
+                    size,
+                    common,
+                }))
             }
-            Type::Content => {
-                let common = CommonDownload::new(self.path, mtime);
-                let content = self.ws_content.content.unwrap();
+            (Type::Content, None) => {
+                let common = CommonDownload::new(path, mtime);
+                let content = ws_content.content.unwrap();
                 SyncStatus::Downloadable(Download::Content(ContentDownload { content, common }))
             }
-            Type::Folder => SyncStatus::NotSupported(Type::Folder, self.path),
+            (Type::Folder, _) => SyncStatus::NotSupported(Type::Folder, path),
         }
     }
 
-    pub async fn sync(self) -> SyncStatus {
-        let latest_path = latest_path(self.path.clone()).await.unwrap();
-        match cmp_mtime(&latest_path, &self.mtime()).await.ok() {
-            None | Some(Ordering::Less) | Some(Ordering::Greater) => self.download(),
-            Some(Ordering::Equal) => SyncStatus::UpToDate(latest_path),
+    /// Determines whether the content is up to date.
+    ///
+    /// Resources of text are localized if they belong to the site at
+    /// `site_url`.
+    pub async fn sync(self, site_url: &Url) -> SyncStatus {
+        match self.source {
+            Source::Ws {
+                ws_content,
+                export,
+                url_format,
+            } => {
+                let latest_path = latest_path(self.path.clone()).await.unwrap();
+                let mtime = ws_content.modified.into();
+                match cmp_mtime(&latest_path, &mtime).await.ok() {
+                    None | Some(Ordering::Less) | Some(Ordering::Greater) => {
+                        Self::download(*ws_content, export, url_format, self.path)
+                    }
+                    Some(Ordering::Equal) => SyncStatus::UpToDate(latest_path),
+                }
+            }
+            Source::Text {
+                text,
+                source_format,
+                format,
+            } => {
+                let resource_dir = resource_dir_name(&self.path);
+                let exported = export_text(&text, source_format, format, &resource_dir, site_url);
+                let up_to_date = match exported {
+                    Ok(exported) => fs::read(&self.path)
+                        .await
+                        .is_ok_and(|current| current == exported.text.as_bytes()),
+                    Err(_) => false,
+                };
+                if up_to_date {
+                    SyncStatus::UpToDate(self.path)
+                } else {
+                    let common = CommonDownload::overwriting(self.path, SystemTime::now());
+                    let size = text.len() as u64;
+                    let source = HtmlSource::Inline(text, source_format);
+                    SyncStatus::Downloadable(Download::Html(HtmlDownload {
+                        source,
+                        format,
+                        size,
+                        common,
+                    }))
+                }
+            }
         }
     }
 }
@@ -108,6 +346,7 @@ pub enum Download {
     File(FileDownload),
     Url(UrlDownload),
     Content(ContentDownload),
+    Html(HtmlDownload),
 }
 
 impl Download {
@@ -116,6 +355,7 @@ impl Download {
             Download::File(file_download) => file_download.size(),
             Download::Url(url_download) => url_download.size() as u64,
             Download::Content(content_download) => content_download.size() as u64,
+            Download::Html(html_download) => html_download.size(),
         }
     }
 }
@@ -152,20 +392,25 @@ pub struct FileDownload {
 }
 
 impl FileDownload {
+    /// Downloads the file, with the token only if it belongs to the site at
+    /// `site_url`.
     pub async fn run(
         &mut self,
+        http: &reqwest::Client,
+        site_url: &Url,
         token: &Token,
         mut report_progress: impl FnMut(u64) + Send,
     ) -> io::Result<()> {
         let (mut file, path) = self.common.create_file().await?;
-        token.apply(&mut self.url);
-        let mut response = util::shared_http()
+        authorize(&mut self.url, site_url, token);
+        let mut response = http
             .get(self.url.clone())
             .send()
             .await
-            .unwrap();
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(io::Error::other)?;
         let mut progress = 0;
-        while let Some(chunk) = response.chunk().await.unwrap() {
+        while let Some(chunk) = response.chunk().await.map_err(io::Error::other)? {
             file.write_all(&chunk).await?;
             progress += chunk.len() as u64;
             report_progress(progress);
@@ -188,18 +433,38 @@ impl FileDownload {
 #[derive(Debug)]
 pub struct UrlDownload {
     url: Url,
+    name: String,
+    format: UrlFormat,
     common: CommonDownload,
 }
 
 impl UrlDownload {
     pub async fn run(&mut self) -> io::Result<()> {
         let (mut file, path) = self.common.create_file().await?;
-        let buf = format!(include_str!("url_format.html"), url = self.url);
-        file.write_all(buf.as_bytes()).await?;
+        file.write_all(self.render().as_bytes()).await?;
         self.common.finish(file, path).await?;
         Ok(())
     }
 
+    fn render(&self) -> String {
+        let url = &self.url;
+        match self.format {
+            UrlFormat::Html | UrlFormat::Bookmarks => {
+                format!(include_str!("url_format.html"), url = url)
+            }
+            UrlFormat::InternetShortcut => format!(include_str!("url_format.url"), url = url),
+            UrlFormat::Desktop => {
+                // Line breaks would end the entry.
+                let name = self.name.replace(['\n', '\r'], " ");
+                format!(include_str!("url_format.desktop"), name = name, url = url)
+            }
+            UrlFormat::Webloc => {
+                let url = html_escape::encode_text(url.as_str());
+                format!(include_str!("url_format.webloc"), url = url)
+            }
+        }
+    }
+
     #[must_use]
     pub fn path(&self) -> &Path {
         self.common.dst_path.as_path()
@@ -207,19 +472,188 @@ impl UrlDownload {
 
     #[must_use]
     pub fn size(&self) -> usize {
-        include_str!("url_format.html").len() - "{url}".len() + self.url.as_str().len()
+        self.render().len()
+    }
+}
+
+#[derive(Debug)]
+enum HtmlSource {
+    Url(Url),
+    Inline(String, SummaryFormat),
+}
+
+/// An HTML document whose `pluginfile.php` resources are downloaded next to
+/// it, so that it can be viewed offline.
+#[derive(Debug)]
+pub struct HtmlDownload {
+    source: HtmlSource,
+    format: Option<TextFormat>,
+    size: u64,
+    common: CommonDownload,
+}
+
+impl HtmlDownload {
+    /// Downloads the document and its resources of the site at `site_url`,
+    /// which are the only ones receiving the token.
+    pub async fn run(
+        &mut self,
+        http: &reqwest::Client,
+        site_url: &Url,
+        token: &Token,
+    ) -> io::Result<()> {
+        let (text, source_format) = match &self.source {
+            HtmlSource::Url(url) => {
+                let mut url = url.clone();
+                authorize(&mut url, site_url, token);
+                let html = http
+                    .get(url)
+                    .send()
+                    .await
+                    .and_then(reqwest::Response::error_for_status)
+                    .map_err(io::Error::other)?
+                    .text()
+                    .await
+                    .map_err(io::Error::other)?;
+                (html, SummaryFormat::Html)
+            }
+            HtmlSource::Inline(text, source_format) => (text.clone(), *source_format),
+        };
+
+        let resource_dir = resource_dir_name(&self.common.dst_path);
+        let exported = export_text(&text, source_format, self.format, &resource_dir, site_url)?;
+        let resource_dir = self.common.dst_path.with_file_name(resource_dir);
+        for resource in &exported.resources {
+            download_resource(http, resource, &resource_dir, site_url, token).await?;
+        }
+
+        let (mut file, path) = self.common.create_file().await?;
+        file.write_all(exported.text.as_bytes()).await?;
+        self.common.finish(file, path).await?;
+        Ok(())
+    }
+
+    #[must_use]
+    pub const fn size(&self) -> u64 {
+        self.size
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        self.common.dst_path.as_path()
     }
 }
 
+/// Converts `text` to `format` and rewrites its resource links of the site at
+/// `site_url` to `resource_dir`.
+///
+/// HTML sources are localized and converted, while Markdown and plain text
+/// sources are kept as they are for Markdown and preformatted for HTML.
+/// Without a format, the text is kept as it is.
+fn export_text(
+    text: &str,
+    source_format: SummaryFormat,
+    format: Option<TextFormat>,
+    resource_dir: &str,
+    site_url: &Url,
+) -> io::Result<html::Localized> {
+    let Some(format) = format else {
+        return Ok(html::Localized {
+            text: text.to_string(),
+            resources: Vec::new(),
+        });
+    };
+
+    match source_format {
+        SummaryFormat::Html | SummaryFormat::Moodle => {
+            let mut localized =
+                html::localize(text, resource_dir, site_url).map_err(io::Error::other)?;
+            if format == TextFormat::Markdown {
+                localized.text = html::to_markdown(&localized.text)?;
+            }
+            Ok(localized)
+        }
+        SummaryFormat::Markdown | SummaryFormat::Plain => {
+            let text = match format {
+                TextFormat::Html => format!("<pre>{}</pre>", html_escape::encode_text(text)),
+                TextFormat::Markdown => text.to_string(),
+            };
+            Ok(html::Localized {
+                text,
+                resources: Vec::new(),
+            })
+        }
+    }
+}
+
+/// The name of the directory containing the resources of an HTML document,
+/// following the convention of browsers saving complete web pages.
+fn resource_dir_name(html_path: &Path) -> String {
+    let stem = html_path.file_stem().unwrap().to_string_lossy();
+    format!("{stem}_files")
+}
+
+/// Appends the token to `url` if it belongs to the site at `site_url`.
+///
+/// Other hosts must never receive the token, even if they serve a
+/// `pluginfile.php`.
+fn authorize(url: &mut Url, site_url: &Url, token: &Token) {
+    if html::is_site_url(url, site_url) {
+        token.apply(url);
+    }
+}
+
+async fn download_resource(
+    http: &reqwest::Client,
+    resource: &html::Resource,
+    resource_dir: &Path,
+    site_url: &Url,
+    token: &Token,
+) -> io::Result<()> {
+    let mut url = resource.url.clone();
+    authorize(&mut url, site_url, token);
+    let mut response = http
+        .get(url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(io::Error::other)?;
+
+    fs::create_dir_all(resource_dir).await?;
+    let path = resource_dir.join(&resource.file_name);
+    let mut dl_path = path.clone();
+    dl_path.push_file_name_suffix(".tmp");
+    let mut file = File::create(&dl_path).await?;
+    while let Some(chunk) = response.chunk().await.map_err(io::Error::other)? {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    fs::rename(dl_path, path).await?;
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct CommonDownload {
     dst_path: PathBuf,
     mtime: SystemTime,
+    /// Replace the destination instead of keeping previous versions.
+    overwrite: bool,
 }
 
 impl CommonDownload {
     fn new(dst_path: PathBuf, mtime: SystemTime) -> Self {
-        Self { dst_path, mtime }
+        Self {
+            dst_path,
+            mtime,
+            overwrite: false,
+        }
+    }
+
+    fn overwriting(dst_path: PathBuf, mtime: SystemTime) -> Self {
+        Self {
+            dst_path,
+            mtime,
+            overwrite: true,
+        }
     }
 
     async fn create_file(&self) -> io::Result<(File, PathBuf)> {
@@ -244,6 +678,12 @@ impl CommonDownload {
     }
 
     async fn finish(&mut self, mut file: File, dl_path: PathBuf) -> io::Result<()> {
+        if self.overwrite {
+            file_set_modified(file, self.mtime).await?;
+            fs::rename(dl_path, &self.dst_path).await?;
+            return Ok(());
+        }
+
         let latest_path = latest_path(self.dst_path.clone()).await?;
         match cmp_mtime(&latest_path, &self.mtime).await.ok() {
             Some(Ordering::Equal) => unreachable!(),
@@ -316,7 +756,8 @@ fn alt_paths(path: &Path) -> impl Iterator<Item = PathBuf> + '_ {
     })
 }
 
-async fn latest_path(path: PathBuf) -> io::Result<PathBuf> {
+/// The path of the latest version of the file at `path`.
+pub(crate) async fn latest_path(path: PathBuf) -> io::Result<PathBuf> {
     let mut latest_path = path.clone();
     for path in alt_paths(&path) {
         if fs::try_exists(&path).await? {
@@ -336,3 +777,66 @@ async fn next_path(path: PathBuf) -> io::Result<PathBuf> {
     }
     unreachable!()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url_download(format: UrlFormat) -> UrlDownload {
+        UrlDownload {
+            url: "https://example.com/?a=1&b=2".parse().unwrap(),
+            name: "Recording\nWeek 1".to_string(),
+            format,
+            common: CommonDownload::new(PathBuf::from("link"), SystemTime::UNIX_EPOCH),
+        }
+    }
+
+    #[test]
+    fn authorize_test() {
+        let site_url = "https://example.com/moodle/".parse().unwrap();
+        let token = "6191f7ea9da0a4aed1cc9ddb23bf4aa7".parse().unwrap();
+
+        let mut url = "https://example.com/webservice/pluginfile.php/1/a/b/x.pdf"
+            .parse()
+            .unwrap();
+        authorize(&mut url, &site_url, &token);
+        assert_eq!(url.query(), Some("token=6191f7ea9da0a4aed1cc9ddb23bf4aa7"));
+
+        let mut url = "https://example.org/webservice/pluginfile.php/1/a/b/x.pdf"
+            .parse()
+            .unwrap();
+        authorize(&mut url, &site_url, &token);
+        assert_eq!(url.query(), None);
+    }
+
+    #[test]
+    fn export_text_foreign_host_test() {
+        let html = r#"<img src="https://example.org/pluginfile.php/1/mod_page/content/2/x.png">"#;
+        let site_url = "https://example.com/".parse().unwrap();
+        let exported = export_text(
+            html,
+            SummaryFormat::Html,
+            Some(TextFormat::Html),
+            "index_files",
+            &site_url,
+        )
+        .unwrap();
+        assert_eq!(exported.text, html);
+        assert!(exported.resources.is_empty());
+    }
+
+    #[test]
+    fn url_render_test() {
+        assert_eq!(
+            url_download(UrlFormat::InternetShortcut).render(),
+            "[InternetShortcut]\r\nURL=https://example.com/?a=1&b=2\r\n"
+        );
+        assert_eq!(
+            url_download(UrlFormat::Desktop).render(),
+            "[Desktop Entry]\nType=Link\nName=Recording Week 1\nURL=https://example.com/?a=1&b=2\nIcon=text-html\n"
+        );
+        assert!(url_download(UrlFormat::Webloc)
+            .render()
+            .contains("<string>https://example.com/?a=1&amp;b=2</string>"));
+    }
+}
@@ -0,0 +1,210 @@
+//! Offline export of HTML content.
+
+use std::{collections::HashMap, io};
+
+use lol_html::{element, errors::RewritingError, rewrite_str, RewriteStrSettings};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use url::Url;
+
+use crate::util::sanitize_path_component;
+
+/// Characters to escape in a relative path segment inside an HTML attribute.
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Attributes that may reference embedded or linked resources.
+const ATTRIBUTES: [&str; 4] = ["src", "href", "poster", "data"];
+
+/// A `pluginfile.php` resource referenced by an HTML document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resource {
+    /// Downloadable resource url (without token).
+    pub url: Url,
+    /// The file name inside the resource directory.
+    pub file_name: String,
+}
+
+/// A document with rewritten resource links.
+#[derive(Debug, PartialEq)]
+pub struct Localized {
+    pub text: String,
+    pub resources: Vec<Resource>,
+}
+
+/// Whether `url` has the same origin as the site at `site_url`.
+///
+/// Only urls of the site itself may receive the token of an account.
+#[must_use]
+pub fn is_site_url(url: &Url, site_url: &Url) -> bool {
+    url.origin() == site_url.origin()
+}
+
+/// Returns the web service variant of a `pluginfile.php` url of the site at
+/// `site_url`.
+///
+/// Moodle serves files to web service clients via
+/// `webservice/pluginfile.php`, which accepts the token as a query parameter.
+/// Files of other hosts are not considered site files.
+#[must_use]
+pub fn pluginfile_url(url: &Url, site_url: &Url) -> Option<Url> {
+    if !is_site_url(url, site_url) {
+        return None;
+    }
+    let path = url.path();
+    let index = path.find("/pluginfile.php/")?;
+    let mut url = url.clone();
+    if !path[..index].ends_with("/webservice") {
+        let path = format!("{}/webservice{}", &path[..index], &path[index..]);
+        url.set_path(&path);
+    }
+    url.set_query(None);
+    url.set_fragment(None);
+    Some(url)
+}
+
+fn file_name(url: &Url) -> String {
+    let segment = url
+        .path_segments()
+        .and_then(Iterator::last)
+        .filter(|segment| !segment.is_empty())
+        .unwrap_or("file");
+    let decoded = percent_decode_str(segment).decode_utf8_lossy();
+    sanitize_path_component(&decoded).into_owned()
+}
+
+/// Rewrites all `pluginfile.php` links of the site at `site_url` in `html` to
+/// files inside `resource_dir`, which is relative to the document.
+pub fn localize(
+    html: &str,
+    resource_dir: &str,
+    site_url: &Url,
+) -> Result<Localized, RewritingError> {
+    let mut resources = Vec::<Resource>::new();
+    let mut local_paths = HashMap::<Url, String>::new();
+
+    let mut local_path = |value: &str| -> Option<String> {
+        let url = pluginfile_url(&Url::parse(value).ok()?, site_url)?;
+        if let Some(local_path) = local_paths.get(&url) {
+            return Some(local_path.clone());
+        }
+
+        let mut file_name = file_name(&url);
+        if resources
+            .iter()
+            .any(|resource| resource.file_name == file_name)
+        {
+            file_name = format!("{}_{file_name}", resources.len());
+        }
+        let local_path = format!(
+            "{resource_dir}/{}",
+            utf8_percent_encode(&file_name, SEGMENT)
+        );
+        resources.push(Resource {
+            url: url.clone(),
+            file_name,
+        });
+        local_paths.insert(url, local_path.clone());
+        Some(local_path)
+    };
+
+    let html = rewrite_str(
+        html,
+        RewriteStrSettings::new().append_element_content_handler(element!("*", |el| {
+            for attribute in ATTRIBUTES {
+                if let Some(local_path) = el
+                    .get_attribute(attribute)
+                    .and_then(|value| local_path(&value))
+                {
+                    el.set_attribute(attribute, &local_path)?;
+                }
+            }
+            Ok(())
+        })),
+    )?;
+
+    Ok(Localized {
+        text: html,
+        resources,
+    })
+}
+
+/// Converts HTML to Markdown.
+///
+/// Links and image sources are kept as they are, so localized documents still
+/// reference the synced files.
+pub fn to_markdown(html: &str) -> io::Result<String> {
+    htmd::convert(html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site_url() -> Url {
+        "https://example.com/".parse().unwrap()
+    }
+
+    #[test]
+    fn localize_test() {
+        let html = r#"<p><img src="https://example.com/webservice/pluginfile.php/1/mod_page/content/2/a%20b.png?forcedownload=1"><a href="https://example.com/pluginfile.php/1/mod_page/content/2/doc.pdf">doc</a><a href="https://example.org/">link</a><img src="https://example.com/webservice/pluginfile.php/1/mod_page/content/2/a%20b.png"></p>"#;
+        let localized = localize(html, "index_files", &site_url()).unwrap();
+        assert_eq!(
+            localized.text,
+            r#"<p><img src="index_files/a%20b.png"><a href="index_files/doc.pdf">doc</a><a href="https://example.org/">link</a><img src="index_files/a%20b.png"></p>"#
+        );
+        assert_eq!(
+            localized.resources,
+            [
+                Resource {
+                    url: "https://example.com/webservice/pluginfile.php/1/mod_page/content/2/a%20b.png"
+                        .parse()
+                        .unwrap(),
+                    file_name: "a b.png".to_string(),
+                },
+                Resource {
+                    url: "https://example.com/webservice/pluginfile.php/1/mod_page/content/2/doc.pdf"
+                        .parse()
+                        .unwrap(),
+                    file_name: "doc.pdf".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn to_markdown_test() {
+        let html = r#"<h3>Week 1</h3><p>See <a href="index_files/doc.pdf">the slides</a>.</p><p><img src="index_files/a%20b.png" alt="diagram"></p>"#;
+        assert_eq!(
+            to_markdown(html).unwrap(),
+            "### Week 1\n\nSee [the \
+             slides](index_files/doc.pdf).\n\n![diagram](index_files/a%20b.png)"
+        );
+    }
+
+    #[test]
+    fn localize_duplicate_names_test() {
+        let html = r#"<img src="https://example.com/webservice/pluginfile.php/1/a/b/1/x.png"><img src="https://example.com/webservice/pluginfile.php/1/a/b/2/x.png">"#;
+        let localized = localize(html, "files", &site_url()).unwrap();
+        assert_eq!(
+            localized.text,
+            r#"<img src="files/x.png"><img src="files/1_x.png">"#
+        );
+    }
+
+    #[test]
+    fn localize_foreign_host_test() {
+        let html = r#"<img src="https://example.org/pluginfile.php/1/mod_page/content/2/x.png"><img src="http://example.com/pluginfile.php/1/mod_page/content/2/x.png">"#;
+        let localized = localize(html, "files", &site_url()).unwrap();
+        assert_eq!(localized.text, html);
+        assert!(localized.resources.is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+//! Settings of the HTTP client used for an account.
+
+use std::{fs, io, path::PathBuf, time::Duration};
+
+pub use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use url::Url;
+
+use crate::util;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("could not read the root certificates from {}", path.display())]
+    ReadCertificates {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("invalid root certificates in {}", path.display())]
+    InvalidCertificates {
+        path: PathBuf,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("invalid proxy {0}")]
+    InvalidProxy(Url, #[source] reqwest::Error),
+    #[error("could not create the HTTP client")]
+    ClientError(#[source] reqwest::Error),
+}
+
+/// How to connect to a site.
+///
+/// The default connects directly with the system's root certificates.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HttpConfig {
+    /// PEM files with root certificates to trust in addition to the system's,
+    /// such as the certificate authority of a university. A leading `~` is
+    /// expanded to the home directory.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub root_certificates: Vec<PathBuf>,
+    /// The proxy for all requests, such as `http://proxy.example.com:3128` or
+    /// `socks5h://localhost:1080`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<Url>,
+    /// The timeout for connecting in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<u64>,
+    /// The timeout for receiving data in seconds.
+    ///
+    /// Unlike a timeout for whole requests, this does not abort large
+    /// downloads on slow connections.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_timeout: Option<u64>,
+    /// The `User-Agent` header sent with each request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+}
+
+impl HttpConfig {
+    #[must_use]
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Creates an HTTP client with these settings.
+    ///
+    /// The default settings share one client. Reading the root certificates
+    /// blocks.
+    pub fn client(&self) -> Result<Client, Error> {
+        if self.is_default() {
+            return Ok(util::shared_http());
+        }
+
+        let mut builder = Client::builder();
+        for path in &self.root_certificates {
+            let pem = fs::read(shellexpand::path::tilde(path)).map_err(|source| {
+                Error::ReadCertificates {
+                    path: path.clone(),
+                    source,
+                }
+            })?;
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem).map_err(|source| {
+                Error::InvalidCertificates {
+                    path: path.clone(),
+                    source,
+                }
+            })?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        if let Some(proxy) = &self.proxy {
+            let proxy = reqwest::Proxy::all(proxy.clone())
+                .map_err(|err| Error::InvalidProxy(proxy.clone(), err))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(Duration::from_secs(connect_timeout));
+        }
+        if let Some(read_timeout) = self.read_timeout {
+            builder = builder.read_timeout(Duration::from_secs(read_timeout));
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        builder.build().map_err(Error::ClientError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_http_config() {
+        let toml = r#"
+            root-certificates = ["/etc/ssl/university-ca.pem"]
+            proxy = "socks5h://localhost:1080"
+            connect-timeout = 10
+            user-agent = "Edu Sync"
+        "#;
+        let config = toml::from_str::<HttpConfig>(toml).unwrap();
+        assert_eq!(
+            config.root_certificates,
+            [Path::new("/etc/ssl/university-ca.pem")]
+        );
+        assert_eq!(config.proxy.as_ref().unwrap().scheme(), "socks5h");
+        assert_eq!(config.connect_timeout, Some(10));
+        assert_eq!(config.read_timeout, None);
+        assert!(!config.is_default());
+
+        let config = HttpConfig {
+            root_certificates: Vec::new(),
+            ..config
+        };
+        config.client().unwrap();
+
+        let config = HttpConfig {
+            root_certificates: vec![PathBuf::from("/nonexistent/ca.pem")],
+            ..config
+        };
+        assert!(matches!(
+            config.client(),
+            Err(Error::ReadCertificates { .. })
+        ));
+
+        assert!(toml::from_str::<HttpConfig>("").unwrap().is_default());
+    }
+}
@@ -7,6 +7,12 @@
 #![deny(rustdoc::all)]
 
 pub mod account;
+pub mod archive;
 pub mod config;
 pub mod content;
+pub(crate) mod html;
+pub mod http;
+pub(crate) mod links;
+pub mod rules;
+pub mod secret;
 pub(crate) mod util;
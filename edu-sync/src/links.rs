@@ -0,0 +1,499 @@
+//! Links found in courses.
+
+use std::{
+    cell::RefCell,
+    fmt::{self, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use lol_html::{element, rewrite_str, text, RewriteStrSettings};
+use regex::Regex;
+use serde::Serialize;
+use tokio::{fs, io};
+use url::Url;
+
+use crate::{content, html, util::PathBufExt};
+
+/// The place a link was found at.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Origin {
+    /// A URL module.
+    Url,
+    /// The summary of a section.
+    Summary,
+    /// The description of a module.
+    Description,
+    /// An exported document, such as a page.
+    Document(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Url => f.write_str("URL"),
+            Self::Summary => f.write_str("section summary"),
+            Self::Description => f.write_str("description"),
+            Self::Document(name) => f.write_str(name),
+        }
+    }
+}
+
+/// A link together with the place it was found at.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Link {
+    pub section: String,
+    pub module: Option<String>,
+    pub text: String,
+    pub url: Url,
+    pub origin: Origin,
+}
+
+/// Whether the url points to a resource outside of the synced files of the
+/// site at `site_url`.
+fn is_external(url: &Url, site_url: &Url) -> bool {
+    matches!(url.scheme(), "http" | "https") && html::pluginfile_url(url, site_url).is_none()
+}
+
+fn text_or_url(text: &str, url: Url) -> (String, Url) {
+    let text = text.trim();
+    if text.is_empty() {
+        (url.to_string(), url)
+    } else {
+        (text.to_string(), url)
+    }
+}
+
+/// Returns the text and target of all external links in an HTML document of
+/// the site at `site_url`.
+pub fn from_html(html: &str, site_url: &Url) -> Vec<(String, Url)> {
+    let links = RefCell::new(Vec::<(String, Option<Url>)>::new());
+    let res = rewrite_str(
+        html,
+        RewriteStrSettings::new()
+            .append_element_content_handler(element!("a[href]", |el| {
+                let url = el
+                    .get_attribute("href")
+                    .and_then(|href| {
+                        Url::parse(html_escape::decode_html_entities(href.trim()).as_ref()).ok()
+                    })
+                    .filter(|url| is_external(url, site_url));
+                links.borrow_mut().push((String::new(), url));
+                Ok(())
+            }))
+            .append_element_content_handler(text!("a[href]", |chunk| {
+                if let Some((text, _)) = links.borrow_mut().last_mut() {
+                    text.push_str(chunk.as_str());
+                }
+                Ok(())
+            })),
+    );
+    if res.is_err() {
+        return Vec::new();
+    }
+
+    links
+        .into_inner()
+        .into_iter()
+        .filter_map(|(text, url)| {
+            let text = html_escape::decode_html_entities(&text);
+            Some(text_or_url(&text, url?))
+        })
+        .collect()
+}
+
+/// Returns the text and target of all external links in a Markdown document
+/// of the site at `site_url`.
+pub fn from_markdown(markdown: &str, site_url: &Url) -> Vec<(String, Url)> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+
+    RE.get_or_init(|| {
+        Regex::new(r#"\[([^\]]*)\]\((?:<([^>]+)>|([^)\s]+))(?:\s+"[^"]*")?\)"#).unwrap()
+    })
+    .captures_iter(markdown)
+    .filter_map(|captures| {
+        let url = captures.get(2).or_else(|| captures.get(3))?.as_str();
+        let url = Url::parse(url)
+            .ok()
+            .filter(|url| is_external(url, site_url))?;
+        Some(text_or_url(&captures[1], url))
+    })
+    .collect()
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.chars()
+        .fold(String::with_capacity(text.len()), |mut escaped, c| {
+            if matches!(c, '\\' | '[' | ']' | '*' | '_' | '`') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+            escaped
+        })
+}
+
+/// Calls `group` whenever the section or module changes and `item` for every
+/// link.
+///
+/// `group` receives the section only if it changed.
+fn for_each_grouped<'a>(
+    links: &'a [Link],
+    mut group: impl FnMut(Option<&'a str>, Option<&'a str>),
+    mut item: impl FnMut(&'a Link),
+) {
+    let mut current: Option<(&String, &Option<String>)> = None;
+    for link in links {
+        let section_changed = current.is_none_or(|(section, _)| section != &link.section);
+        let module_changed = current.is_none_or(|(_, module)| module != &link.module);
+        if section_changed || module_changed {
+            let section = section_changed.then_some(link.section.as_str());
+            group(section, link.module.as_deref());
+            current = Some((&link.section, &link.module));
+        }
+        item(link);
+    }
+}
+
+/// Renders links as a Markdown list grouped by section and module.
+pub fn to_markdown(links: &[Link]) -> String {
+    let markdown = RefCell::new("# Links\n".to_string());
+    for_each_grouped(
+        links,
+        |section, module| {
+            let mut markdown = markdown.borrow_mut();
+            if let Some(section) = section {
+                write!(markdown, "\n## {}\n", escape_markdown(section)).unwrap();
+            }
+            match module {
+                Some(module) => write!(markdown, "\n### {}\n\n", escape_markdown(module)).unwrap(),
+                None => markdown.push('\n'),
+            }
+        },
+        |link| {
+            let text = escape_markdown(&link.text);
+            writeln!(markdown.borrow_mut(), "- [{text}](<{}>)", link.url).unwrap();
+        },
+    );
+    markdown.into_inner()
+}
+
+/// Renders links as an HTML document grouped by section and module.
+pub fn to_html(links: &[Link]) -> String {
+    let html = RefCell::new(
+        concat!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n",
+            "<title>Links</title>\n</head>\n<body>\n<h1>Links</h1>\n",
+        )
+        .to_string(),
+    );
+    for_each_grouped(
+        links,
+        |section, module| {
+            let mut html = html.borrow_mut();
+            if html.ends_with("</li>\n") {
+                html.push_str("</ul>\n");
+            }
+            if let Some(section) = section {
+                let section = html_escape::encode_text(section);
+                writeln!(html, "<h2>{section}</h2>").unwrap();
+            }
+            if let Some(module) = module {
+                let module = html_escape::encode_text(module);
+                writeln!(html, "<h3>{module}</h3>").unwrap();
+            }
+            html.push_str("<ul>\n");
+        },
+        |link| {
+            let url = html_escape::encode_double_quoted_attribute(link.url.as_str());
+            let text = html_escape::encode_text(&link.text);
+            let origin = link.origin.to_string();
+            let origin = html_escape::encode_text(&origin);
+            writeln!(
+                html.borrow_mut(),
+                "<li><a href=\"{url}\">{text}</a> ({origin})</li>"
+            )
+            .unwrap();
+        },
+    );
+    let mut html = html.into_inner();
+    if html.ends_with("</li>\n") {
+        html.push_str("</ul>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Renders links as a JSON array.
+pub fn to_json(links: &[Link]) -> String {
+    serde_json::to_string_pretty(links).unwrap()
+}
+
+/// An exported document whose links are read from its synced copy.
+#[derive(Debug, Clone)]
+pub(crate) struct Document {
+    /// The position of the links of the document among the other links.
+    pub position: usize,
+    pub section: String,
+    pub module: Option<String>,
+    pub origin: Origin,
+    pub path: PathBuf,
+}
+
+/// The index of all external links of a course, written to `links.html` and
+/// `links.json` in the course directory.
+///
+/// As links of exported documents are read from their local copies, the index
+/// has to be written after the contents of the course have been synced.
+#[derive(Debug)]
+pub struct LinkIndex {
+    path: PathBuf,
+    site_url: Url,
+    links: Vec<Link>,
+    documents: Vec<Document>,
+}
+
+impl LinkIndex {
+    pub(crate) const fn new(
+        path: PathBuf,
+        site_url: Url,
+        links: Vec<Link>,
+        documents: Vec<Document>,
+    ) -> Self {
+        Self {
+            path,
+            site_url,
+            links,
+            documents,
+        }
+    }
+
+    /// The course directory the index is written to.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the links of the synced documents and writes the index if it
+    /// changed.
+    pub async fn write(mut self) -> io::Result<()> {
+        for document in self.documents.into_iter().rev() {
+            let path = content::latest_path(document.path).await?;
+            let Ok(text) = fs::read_to_string(&path).await else {
+                continue;
+            };
+            let links = if path.extension().is_some_and(|extension| extension == "md") {
+                from_markdown(&text, &self.site_url)
+            } else {
+                from_html(&text, &self.site_url)
+            };
+            let links = links.into_iter().map(|(text, url)| Link {
+                section: document.section.clone(),
+                module: document.module.clone(),
+                text,
+                url,
+                origin: document.origin.clone(),
+            });
+            self.links
+                .splice(document.position..document.position, links);
+        }
+
+        if self.links.is_empty() {
+            return Ok(());
+        }
+        write_if_changed(&self.path.join("links.html"), &to_html(&self.links)).await?;
+        write_if_changed(&self.path.join("links.json"), &to_json(&self.links)).await
+    }
+}
+
+async fn write_if_changed(path: &Path, text: &str) -> io::Result<()> {
+    if fs::read(path)
+        .await
+        .is_ok_and(|current| current == text.as_bytes())
+    {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let mut tmp_path = path.to_path_buf();
+    tmp_path.push_file_name_suffix(".tmp");
+    fs::write(&tmp_path, text).await?;
+    fs::rename(tmp_path, path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(section: &str, module: Option<&str>, text: &str, url: &str) -> Link {
+        Link {
+            section: section.to_string(),
+            module: module.map(str::to_string),
+            text: text.to_string(),
+            url: url.parse().unwrap(),
+            origin: Origin::Url,
+        }
+    }
+
+    #[test]
+    fn from_html_test() {
+        let html = r#"<p>The <a href="https://example.org/rec?id=5&amp;t=1"><b>recording</b> of week 5</a>,
+            <a href="https://example.com/webservice/pluginfile.php/1/mod_page/content/2/a.pdf">slides</a>,
+            <a href="https://example.org/pluginfile.php/3/mod_page/content/4/b.pdf">other</a>,
+            <a href="mailto:teacher@example.com">mail</a>,
+            <a href=" https://example.org/ "></a></p>"#;
+        assert_eq!(
+            from_html(html, &"https://example.com/".parse().unwrap()),
+            [
+                (
+                    "recording of week 5".to_string(),
+                    "https://example.org/rec?id=5&t=1".parse().unwrap()
+                ),
+                (
+                    "other".to_string(),
+                    "https://example.org/pluginfile.php/3/mod_page/content/4/b.pdf"
+                        .parse()
+                        .unwrap()
+                ),
+                (
+                    "https://example.org/".to_string(),
+                    "https://example.org/".parse().unwrap()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn from_markdown_test() {
+        let markdown = "See [the recording](https://example.org/rec \"Recording\") and \
+                        [slides](index_files/a.pdf) or [this](<https://example.org/a_(b)>).";
+        assert_eq!(
+            from_markdown(markdown, &"https://example.com/".parse().unwrap()),
+            [
+                (
+                    "the recording".to_string(),
+                    "https://example.org/rec".parse().unwrap()
+                ),
+                (
+                    "this".to_string(),
+                    "https://example.org/a_(b)".parse().unwrap()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn to_markdown_test() {
+        let links = [
+            link("Week 1", None, "Schedule", "https://example.com/0"),
+            link(
+                "Week 1",
+                Some("Recordings"),
+                "Recording [1]",
+                "https://example.com/1",
+            ),
+            link(
+                "Week 1",
+                Some("Recordings"),
+                "Recording [2]",
+                "https://example.com/2",
+            ),
+            link(
+                "Week 2",
+                Some("Slides"),
+                "slides_2",
+                "https://example.com/a_(b)",
+            ),
+        ];
+        let expected = r"# Links
+
+## Week 1
+
+- [Schedule](<https://example.com/0>)
+
+### Recordings
+
+- [Recording \[1\]](<https://example.com/1>)
+- [Recording \[2\]](<https://example.com/2>)
+
+## Week 2
+
+### Slides
+
+- [slides\_2](<https://example.com/a_(b)>)
+";
+        assert_eq!(to_markdown(&links), expected);
+    }
+
+    #[test]
+    fn to_html_test() {
+        let links = [
+            link("Week <1>", None, "Schedule", "https://example.com/0"),
+            link(
+                "Week <1>",
+                Some("Recordings"),
+                "Recording",
+                "https://example.com/1?a=1&b=2",
+            ),
+        ];
+        let expected = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Links</title>
+</head>
+<body>
+<h1>Links</h1>
+<h2>Week &lt;1&gt;</h2>
+<ul>
+<li><a href="https://example.com/0">Schedule</a> (URL)</li>
+</ul>
+<h3>Recordings</h3>
+<ul>
+<li><a href="https://example.com/1?a=1&amp;b=2">Recording</a> (URL)</li>
+</ul>
+</body>
+</html>
+"#;
+        assert_eq!(to_html(&links), expected);
+    }
+
+    #[tokio::test]
+    async fn link_index_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let page_path = dir.path().join("page.html");
+        std::fs::write(
+            &page_path,
+            r#"<a href="https://example.org/rec">recording</a>"#,
+        )
+        .unwrap();
+        let document = Document {
+            position: 1,
+            section: "Week 1".to_string(),
+            module: Some("Page".to_string()),
+            origin: Origin::Document("page.html".to_string()),
+            path: page_path,
+        };
+        let links = vec![
+            link("Week 1", None, "Schedule", "https://example.org/0"),
+            link("Week 2", None, "Slides", "https://example.org/2"),
+        ];
+        let index = LinkIndex::new(
+            dir.path().to_path_buf(),
+            "https://example.com/".parse().unwrap(),
+            links,
+            vec![document],
+        );
+        index.write().await.unwrap();
+
+        let json = std::fs::read_to_string(dir.path().join("links.json")).unwrap();
+        let texts = serde_json::from_str::<Vec<serde_json::Value>>(&json)
+            .unwrap()
+            .into_iter()
+            .map(|link| link["text"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["Schedule", "recording", "Slides"]);
+        assert!(dir.path().join("links.html").exists());
+    }
+}
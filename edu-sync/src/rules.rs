@@ -0,0 +1,289 @@
+//! Rules that enable or disable courses automatically on fetch.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use edu_ws::response::{category::Category, course::Course};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use thiserror::Error;
+use time::{Date, Duration, Month, OffsetDateTime};
+
+use crate::{config::Pattern, util};
+
+/// The names of a category and its ancestors, starting at the top level.
+pub type CategoryPath = Vec<String>;
+
+/// Resolves the paths of all categories.
+#[must_use]
+pub fn category_paths(categories: Vec<Category>) -> BTreeMap<u64, CategoryPath> {
+    let names = categories
+        .iter()
+        .map(|category| (category.id, category.name.as_str()))
+        .collect::<BTreeMap<_, _>>();
+    categories
+        .iter()
+        .map(|category| {
+            let path = category
+                .path
+                .split('/')
+                .filter_map(|id| id.parse().ok())
+                .filter_map(|id| names.get(&id))
+                .map(|name| (*name).to_string())
+                .collect();
+            (category.id, path)
+        })
+        .collect()
+}
+
+#[derive(Error, Debug)]
+#[error("invalid day of the year {0:?}, expected the format MM-DD")]
+pub struct ParseMonthDayError(String);
+
+/// A day of the year such as `10-01`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MonthDay {
+    month: Month,
+    day: u8,
+}
+
+impl MonthDay {
+    /// The date of this day in `year`, if it exists.
+    fn in_year(self, year: i32) -> Option<Date> {
+        Date::from_calendar_date(year, self.month, self.day).ok()
+    }
+}
+
+impl FromStr for MonthDay {
+    type Err = ParseMonthDayError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseMonthDayError(s.to_string());
+        let (month, day) = s.split_once('-').ok_or_else(err)?;
+        let month = month
+            .parse::<u8>()
+            .ok()
+            .and_then(|month| Month::try_from(month).ok())
+            .ok_or_else(err)?;
+        let day = day.parse().map_err(|_| err())?;
+        // A leap year, so that `02-29` is accepted.
+        Date::from_calendar_date(2000, month, day).map_err(|_| err())?;
+        Ok(Self { month, day })
+    }
+}
+
+impl Display for MonthDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}-{:02}", u8::from(self.month), self.day)
+    }
+}
+
+/// Account-level rules for selecting courses.
+///
+/// The rules are evaluated whenever the courses are fetched. Rules for new
+/// courses only apply to courses that are not in the config file yet, so that
+/// manually disabled courses stay disabled.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct Rules {
+    /// Enable new courses whose full or short name matches one of these
+    /// patterns.
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub enable_names: Vec<Pattern>,
+    /// Enable new courses whose category matches one of these patterns.
+    ///
+    /// Patterns are matched against the name of the category and against its
+    /// path, such as `Faculty of Science/Mathematics`.
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub enable_categories: Vec<Pattern>,
+    /// The days of the year terms start at, such as `["04-01", "10-01"]`.
+    ///
+    /// If set, new courses starting in the current term are enabled.
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub term_starts: Vec<MonthDay>,
+    /// Disable courses that ended more than this many days ago.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disable_ended_after_days: Option<u64>,
+    /// Always enable courses marked as favourite.
+    #[serde(default, skip_serializing_if = "util::is_false")]
+    pub sync_favourites: bool,
+}
+
+impl Rules {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.enable_names.is_empty()
+            && self.enable_categories.is_empty()
+            && self.term_starts.is_empty()
+            && self.disable_ended_after_days.is_none()
+            && !self.sync_favourites
+    }
+
+    /// Whether evaluating the rules requires the course categories.
+    #[must_use]
+    pub fn needs_categories(&self) -> bool {
+        !self.enable_categories.is_empty()
+    }
+
+    /// Decides whether a course should be synced, if any rule applies.
+    #[must_use]
+    pub fn sync(
+        &self,
+        course: &Course,
+        category: Option<&CategoryPath>,
+        new: bool,
+        now: OffsetDateTime,
+    ) -> Option<bool> {
+        if self.sync_favourites && course.favourite == Some(true) {
+            return Some(true);
+        }
+
+        if let (Some(days), Some(end_date)) = (self.disable_ended_after_days, date(course.end_date))
+        {
+            let days = i64::try_from(days).unwrap_or(i64::MAX);
+            if end_date.saturating_add(Duration::days(days)) < now.date() {
+                return Some(false);
+            }
+        }
+
+        if !new {
+            return None;
+        }
+        let name_matches = self.enable_names.iter().any(|pattern| {
+            pattern.is_match(&course.full_name) || pattern.is_match(&course.short_name)
+        });
+        let category_matches = category.is_some_and(|path| {
+            let name = path.last().map(String::as_str).unwrap_or_default();
+            let path = path.join("/");
+            self.enable_categories
+                .iter()
+                .any(|pattern| pattern.is_match(name) || pattern.is_match(&path))
+        });
+        let in_current_term = date(course.start_date)
+            .zip(self.current_term(now.date()))
+            .is_some_and(|(start_date, (term_start, term_end))| {
+                term_start <= start_date && start_date < term_end
+            });
+        (name_matches || category_matches || in_current_term).then_some(true)
+    }
+
+    /// The start and the end (exclusive) of the term `today` is in.
+    fn current_term(&self, today: Date) -> Option<(Date, Date)> {
+        let starts = (today.year() - 1..=today.year() + 1)
+            .flat_map(|year| {
+                self.term_starts
+                    .iter()
+                    .filter_map(move |month_day| month_day.in_year(year))
+            })
+            .collect::<Vec<_>>();
+        let term_start = starts.iter().filter(|&&start| start <= today).max()?;
+        let term_end = starts.iter().filter(|&&start| start > today).min()?;
+        Some((*term_start, *term_end))
+    }
+}
+
+/// The date of a course timestamp, which Moodle sets to 0 if unset.
+fn date(timestamp: Option<OffsetDateTime>) -> Option<Date> {
+    timestamp
+        .filter(|timestamp| timestamp.unix_timestamp() > 0)
+        .map(OffsetDateTime::date)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use time::macros::{date, datetime};
+
+    use super::*;
+
+    fn course(full_name: &str, start_date: i64, end_date: i64, favourite: bool) -> Course {
+        serde_json::from_value(json!({
+            "id": 1,
+            "shortname": "short",
+            "fullname": full_name,
+            "idnumber": "",
+            "visible": 1,
+            "startdate": start_date,
+            "enddate": end_date,
+            "isfavourite": favourite,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn month_day_test() {
+        let month_day = "10-01".parse::<MonthDay>().unwrap();
+        assert_eq!(month_day.in_year(2024), Some(date!(2024 - 10 - 01)));
+        assert_eq!(month_day.to_string(), "10-01");
+        assert!("02-29".parse::<MonthDay>().is_ok());
+        assert!("02-30".parse::<MonthDay>().is_err());
+        assert!("13-01".parse::<MonthDay>().is_err());
+        assert!("1001".parse::<MonthDay>().is_err());
+    }
+
+    #[test]
+    fn category_paths_test() {
+        let categories = serde_json::from_value(json!([
+            { "id": 1, "name": "Science", "parent": 0, "path": "/1", "depth": 1 },
+            { "id": 4, "name": "Mathematics", "parent": 1, "path": "/1/4", "depth": 2 },
+        ]))
+        .unwrap();
+        assert_eq!(
+            category_paths(categories),
+            BTreeMap::from([
+                (1, vec!["Science".to_string()]),
+                (4, vec!["Science".to_string(), "Mathematics".to_string()]),
+            ])
+        );
+    }
+
+    #[test]
+    fn rules_test() {
+        let rules = toml::from_str::<Rules>(
+            r#"
+                enable-names = ["*algebra*"]
+                enable-categories = ["Science/*"]
+                term-starts = ["04-01", "10-01"]
+                disable-ended-after-days = 30
+                sync-favourites = true
+            "#,
+        )
+        .unwrap();
+        let now = datetime!(2024-11-15 12:00 UTC);
+        // 2024-10-14 and 2024-05-01
+        let (current, previous) = (1_728_864_000, 1_714_521_600);
+        // 2024-10-01 and 2024-11-01
+        let (long_ago, recently) = (1_727_740_800, 1_730_419_200);
+        let math = vec!["Science".to_string(), "Mathematics".to_string()];
+
+        let linear_algebra = course("Linear Algebra", previous, 0, false);
+        assert_eq!(rules.sync(&linear_algebra, None, true, now), Some(true));
+        assert_eq!(rules.sync(&linear_algebra, None, false, now), None);
+
+        let analysis = course("Analysis", previous, 0, false);
+        assert_eq!(rules.sync(&analysis, None, true, now), None);
+        assert_eq!(rules.sync(&analysis, Some(&math), true, now), Some(true));
+
+        let current_course = course("Analysis", current, 0, false);
+        assert_eq!(rules.sync(&current_course, None, true, now), Some(true));
+
+        let ended = course("Linear Algebra", previous, long_ago, false);
+        assert_eq!(rules.sync(&ended, None, true, now), Some(false));
+        assert_eq!(rules.sync(&ended, None, false, now), Some(false));
+        let recently_ended = course("Analysis", previous, recently, false);
+        assert_eq!(rules.sync(&recently_ended, None, false, now), None);
+
+        let favourite = course("Analysis", previous, long_ago, true);
+        assert_eq!(rules.sync(&favourite, None, false, now), Some(true));
+
+        assert!(Rules::default().is_empty());
+        assert_eq!(Rules::default().sync(&favourite, None, true, now), None);
+    }
+}
@@ -0,0 +1,391 @@
+//! Storage of tokens outside of the config file.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    fs,
+    io::{self, ErrorKind},
+    mem,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use age::secrecy::SecretString;
+use edu_ws::token::Token;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::Config;
+
+/// The service name of keyring entries.
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "edu-sync";
+
+/// Where new tokens are stored.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// The config file itself.
+    #[default]
+    Plaintext,
+    /// The keyring of the operating system, such as the Secret Service.
+    Keyring,
+    /// A file encrypted with a passphrase using age.
+    EncryptedFile,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Plaintext => f.write_str("config file"),
+            Self::Keyring => f.write_str("keyring"),
+            Self::EncryptedFile => f.write_str("encrypted token file"),
+        }
+    }
+}
+
+/// A secret as written to the config file.
+///
+/// This is either the secret itself or the name of the entry in a backend.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum SecretRef<T> {
+    Plaintext(T),
+    Keyring {
+        keyring: String,
+    },
+    EncryptedFile {
+        #[serde(rename = "encrypted-file")]
+        encrypted_file: String,
+    },
+}
+
+pub type TokenRef = SecretRef<Token>;
+
+impl<T> SecretRef<T> {
+    #[must_use]
+    pub const fn backend(&self) -> Backend {
+        match self {
+            Self::Plaintext(_) => Backend::Plaintext,
+            Self::Keyring { .. } => Backend::Keyring,
+            Self::EncryptedFile { .. } => Backend::EncryptedFile,
+        }
+    }
+
+    /// Drops a plaintext secret, keeping what is needed to delete the secret
+    /// from its backend.
+    #[must_use]
+    pub fn erase(self) -> SecretRef<()> {
+        match self {
+            Self::Plaintext(_) => SecretRef::Plaintext(()),
+            Self::Keyring { keyring } => SecretRef::Keyring { keyring },
+            Self::EncryptedFile { encrypted_file } => SecretRef::EncryptedFile { encrypted_file },
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("no secret named {name:?} in the {backend}")]
+    NotFound { backend: Backend, name: String },
+    #[error("invalid secret named {name:?} in the {backend}")]
+    InvalidSecret { backend: Backend, name: String },
+    #[cfg(feature = "keyring")]
+    #[error("keyring error")]
+    KeyringError(#[from] keyring::Error),
+    #[error("keyring support is not enabled in this build")]
+    KeyringUnsupported,
+    #[error("a passphrase is required for the encrypted token file")]
+    PassphraseRequired,
+    #[error("could not decrypt the token file")]
+    DecryptError(#[from] age::DecryptError),
+    #[error("could not encrypt the token file")]
+    EncryptError(#[from] age::EncryptError),
+    #[error("invalid token file")]
+    InvalidFile(#[from] toml::de::Error),
+    #[error("I/O error")]
+    IoError(#[from] io::Error),
+}
+
+/// Access to the secret backends.
+///
+/// Keyring and file operations block.
+pub struct Store {
+    backend: Backend,
+    passphrase: Option<SecretString>,
+    /// The decrypted content of the token file, loaded on first use.
+    file: Option<BTreeMap<String, String>>,
+    file_changed: bool,
+}
+
+impl Store {
+    /// Creates a store that saves new tokens in `backend`.
+    #[must_use]
+    pub const fn new(backend: Backend) -> Self {
+        Self {
+            backend,
+            passphrase: None,
+            file: None,
+            file_changed: false,
+        }
+    }
+
+    /// Sets the passphrase of the encrypted token file.
+    #[must_use]
+    pub fn with_passphrase(mut self, passphrase: String) -> Self {
+        self.passphrase = Some(passphrase.into());
+        self
+    }
+
+    #[must_use]
+    pub const fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// The path of the encrypted token file.
+    #[must_use]
+    pub fn path() -> &'static Path {
+        static TOKENS_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+        TOKENS_PATH
+            .get_or_init(|| Config::path().with_extension("tokens.age"))
+            .as_path()
+    }
+
+    pub fn get<T>(&mut self, secret_ref: &SecretRef<T>) -> Result<T, Error>
+    where
+        T: FromStr + Clone,
+    {
+        let (backend, name, secret) = match secret_ref {
+            SecretRef::Plaintext(secret) => return Ok(secret.clone()),
+            SecretRef::Keyring { keyring } => (Backend::Keyring, keyring, keyring_get(keyring)?),
+            SecretRef::EncryptedFile { encrypted_file } => {
+                let secret =
+                    self.file()?
+                        .get(encrypted_file)
+                        .cloned()
+                        .ok_or_else(|| Error::NotFound {
+                            backend: Backend::EncryptedFile,
+                            name: encrypted_file.clone(),
+                        })?;
+                (Backend::EncryptedFile, encrypted_file, secret)
+            }
+        };
+        secret.parse().map_err(|_| Error::InvalidSecret {
+            backend,
+            name: name.clone(),
+        })
+    }
+
+    /// Saves `secret` under `name` in the backend of this store.
+    ///
+    /// Call [`Store::flush`] afterwards to persist the encrypted token file.
+    pub fn insert<T>(&mut self, name: &str, secret: T) -> Result<SecretRef<T>, Error>
+    where
+        T: Display,
+    {
+        match self.backend {
+            Backend::Plaintext => Ok(SecretRef::Plaintext(secret)),
+            Backend::Keyring => {
+                keyring_set(name, &secret.to_string())?;
+                Ok(SecretRef::Keyring {
+                    keyring: name.to_string(),
+                })
+            }
+            Backend::EncryptedFile => {
+                self.file()?.insert(name.to_string(), secret.to_string());
+                self.file_changed = true;
+                Ok(SecretRef::EncryptedFile {
+                    encrypted_file: name.to_string(),
+                })
+            }
+        }
+    }
+
+    /// Copies a secret into the backend of this store unless it is already
+    /// there.
+    ///
+    /// Returns the previous reference if it changed. The previous copy is kept
+    /// until it is deleted with [`Store::remove_all`], which must only happen
+    /// after the new reference has been persisted.
+    pub fn migrate<T>(
+        &mut self,
+        name: &str,
+        secret_ref: &mut SecretRef<T>,
+    ) -> Result<Option<SecretRef<()>>, Error>
+    where
+        T: FromStr + Display + Clone,
+    {
+        if secret_ref.backend() == self.backend {
+            return Ok(None);
+        }
+        let secret = self.get(secret_ref)?;
+        let new_secret_ref = self.insert(name, secret)?;
+        let old_secret_ref = mem::replace(secret_ref, new_secret_ref);
+        Ok(Some(old_secret_ref.erase()))
+    }
+
+    /// Deletes a secret from its backend.
+    pub fn remove<T>(&mut self, secret_ref: &SecretRef<T>) -> Result<(), Error> {
+        match secret_ref {
+            SecretRef::Plaintext(_) => Ok(()),
+            SecretRef::Keyring { keyring } => keyring_delete(keyring),
+            SecretRef::EncryptedFile { encrypted_file } => {
+                self.file()?.remove(encrypted_file);
+                self.file_changed = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// Deletes secrets from their backends and writes the encrypted token file.
+    pub fn remove_all(&mut self, secret_refs: &[SecretRef<()>]) -> Result<(), Error> {
+        for secret_ref in secret_refs {
+            self.remove(secret_ref)?;
+        }
+        self.flush()
+    }
+
+    /// Writes the encrypted token file if it changed.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if !self.file_changed {
+            return Ok(());
+        }
+        let passphrase = self.passphrase.clone().ok_or(Error::PassphraseRequired)?;
+        let tokens = self.file()?;
+        let ciphertext = encrypt(tokens, passphrase)?;
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("age.tmp");
+        fs::write(&tmp_path, ciphertext)?;
+        fs::rename(tmp_path, path)?;
+        self.file_changed = false;
+        Ok(())
+    }
+
+    fn file(&mut self) -> Result<&mut BTreeMap<String, String>, Error> {
+        if self.file.is_none() {
+            let passphrase = self.passphrase.clone().ok_or(Error::PassphraseRequired)?;
+            let tokens = match fs::read(Self::path()) {
+                Ok(ciphertext) => decrypt(&ciphertext, passphrase)?,
+                Err(err) if err.kind() == ErrorKind::NotFound => BTreeMap::new(),
+                Err(err) => return Err(err.into()),
+            };
+            self.file = Some(tokens);
+        }
+        Ok(self.file.as_mut().unwrap())
+    }
+}
+
+fn encrypt(tokens: &BTreeMap<String, String>, passphrase: SecretString) -> Result<Vec<u8>, Error> {
+    let plaintext = toml::to_string(tokens).unwrap();
+    let recipient = age::scrypt::Recipient::new(passphrase);
+    Ok(age::encrypt(&recipient, plaintext.as_bytes())?)
+}
+
+fn decrypt(ciphertext: &[u8], passphrase: SecretString) -> Result<BTreeMap<String, String>, Error> {
+    let identity = age::scrypt::Identity::new(passphrase);
+    let plaintext = age::decrypt(&identity, ciphertext)?;
+    let plaintext =
+        String::from_utf8(plaintext).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+    Ok(toml::from_str(&plaintext)?)
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_get(name: &str) -> Result<String, Error> {
+    keyring::Entry::new(KEYRING_SERVICE, name)?
+        .get_password()
+        .map_err(|err| match err {
+            keyring::Error::NoEntry => Error::NotFound {
+                backend: Backend::Keyring,
+                name: name.to_string(),
+            },
+            err => err.into(),
+        })
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_set(name: &str, secret: &str) -> Result<(), Error> {
+    keyring::Entry::new(KEYRING_SERVICE, name)?.set_password(secret)?;
+    Ok(())
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_delete(name: &str) -> Result<(), Error> {
+    match keyring::Entry::new(KEYRING_SERVICE, name)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_get(_name: &str) -> Result<String, Error> {
+    Err(Error::KeyringUnsupported)
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_set(_name: &str, _secret: &str) -> Result<(), Error> {
+    Err(Error::KeyringUnsupported)
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_delete(_name: &str) -> Result<(), Error> {
+    Err(Error::KeyringUnsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Account {
+        token: TokenRef,
+    }
+
+    #[test]
+    fn token_ref_deserialization_test() {
+        let token = "6191f7ea9da0a4aed1cc9ddb23bf4aa7".parse().unwrap();
+        assert_eq!(
+            toml::from_str::<Account>("token = \"6191f7ea9da0a4aed1cc9ddb23bf4aa7\"").unwrap(),
+            Account {
+                token: TokenRef::Plaintext(token)
+            }
+        );
+        assert_eq!(
+            toml::from_str::<Account>("token = { keyring = \"1@example.com\" }").unwrap(),
+            Account {
+                token: TokenRef::Keyring {
+                    keyring: "1@example.com".to_string()
+                }
+            }
+        );
+        assert_eq!(
+            toml::from_str::<Account>("token = { encrypted-file = \"1@example.com\" }").unwrap(),
+            Account {
+                token: TokenRef::EncryptedFile {
+                    encrypted_file: "1@example.com".to_string()
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn encryption_test() {
+        let tokens = BTreeMap::from([(
+            "1@example.com".to_string(),
+            "6191f7ea9da0a4aed1cc9ddb23bf4aa7".to_string(),
+        )]);
+        let ciphertext = encrypt(&tokens, "passphrase".to_string().into()).unwrap();
+        assert_eq!(
+            decrypt(&ciphertext, "passphrase".to_string().into()).unwrap(),
+            tokens
+        );
+        assert!(matches!(
+            decrypt(&ciphertext, "wrong".to_string().into()),
+            Err(Error::DecryptError(_))
+        ));
+    }
+}
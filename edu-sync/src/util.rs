@@ -1,7 +1,20 @@
-use std::{borrow::Cow, ffi::OsStr, mem, path::PathBuf, sync::OnceLock};
+use std::{
+    borrow::Cow,
+    env,
+    ffi::OsStr,
+    mem,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
 
 use directories::ProjectDirs;
+use edu_ws::{record::Recorder, token::Token, ws};
 use regex::{NoExpand, Regex};
+use url::Url;
+
+/// The environment variable to record web service responses to a directory
+/// from, as fixtures for tests.
+pub const RECORD_VAR: &str = "EDU_SYNC_RECORD";
 
 pub fn project_dirs() -> &'static ProjectDirs {
     static PROJECT_DIRS: OnceLock<ProjectDirs> = OnceLock::new();
@@ -18,6 +31,26 @@ pub fn shared_http() -> reqwest::Client {
     SHARED.get_or_init(reqwest::Client::new).clone()
 }
 
+/// Creates a web service client, which records responses if requested via
+/// [`RECORD_VAR`].
+pub fn ws_client(
+    http: reqwest::Client,
+    site_url: &Url,
+    token: Token,
+    lang: Option<String>,
+) -> ws::Client {
+    static RECORDER: OnceLock<Option<Arc<Recorder>>> = OnceLock::new();
+
+    let ws_client = ws::Client::new(http, site_url, token, lang);
+    let recorder = RECORDER.get_or_init(|| {
+        env::var_os(RECORD_VAR).map(|dir| Arc::new(Recorder::new(PathBuf::from(dir))))
+    });
+    match recorder {
+        Some(recorder) => ws_client.with_recorder(recorder.clone()),
+        None => ws_client,
+    }
+}
+
 // From https://github.com/rust-lang/rust/blob/1.78.0/library/std/src/path.rs#L342-L364
 fn split_file_at_dot(file: &OsStr) -> (&OsStr, Option<&OsStr>) {
     let slice = file.as_encoded_bytes();
@@ -72,6 +105,10 @@ impl PathBufExt for PathBuf {
     }
 }
 
+pub const fn is_false(value: &bool) -> bool {
+    !*value
+}
+
 pub fn sanitize_path_component(path_component: &str) -> Cow<'_, str> {
     static RE: OnceLock<Regex> = OnceLock::new();
 
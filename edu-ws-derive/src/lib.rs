@@ -7,7 +7,7 @@
 #![deny(rustdoc::all)]
 
 use quote::quote;
-use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, Type};
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitStr, Type};
 
 #[proc_macro_derive(HexWrapper)]
 pub fn hex_wrapper_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
@@ -110,6 +110,63 @@ pub fn from_wrapper_derive(input: proc_macro::TokenStream) -> proc_macro::TokenS
     proc_macro::TokenStream::from(expanded)
 }
 
+/// Implements `edu_ws::ws::WsFunction` for the parameters of a web service
+/// function.
+///
+/// ```ignore
+/// #[derive(Serialize, Debug, WsFunction)]
+/// #[ws_function(name = "core_course_get_contents", response = Vec<Section>)]
+/// struct GetContents {
+///     #[serde(rename = "courseid")]
+///     course_id: u64,
+/// }
+/// ```
+#[proc_macro_derive(WsFunction, attributes(ws_function))]
+pub fn ws_function_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut function = None;
+    let mut response = None;
+    for attr in &input.attrs {
+        if !attr.path().is_ident("ws_function") {
+            continue;
+        }
+        let res = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                function = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else if meta.path.is_ident("response") {
+                response = Some(meta.value()?.parse::<Type>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `name` or `response`"))
+            }
+        });
+        if let Err(err) = res {
+            return err.to_compile_error().into();
+        }
+    }
+    let (Some(function), Some(response)) = (function, response) else {
+        return syn::Error::new_spanned(
+            name,
+            "expected `#[ws_function(name = \"...\", response = ...)]`",
+        )
+        .to_compile_error()
+        .into();
+    };
+
+    let expanded = quote! {
+        impl #impl_generics ::edu_ws::ws::WsFunction for #name #ty_generics #where_clause {
+            const NAME: &'static str = #function;
+
+            type Response = #response;
+        }
+    };
+    proc_macro::TokenStream::from(expanded)
+}
+
 fn inner(data: &Data) -> (&Type, &Option<Ident>) {
     let fields = match *data {
         Data::Struct(ref data) => match data.fields {
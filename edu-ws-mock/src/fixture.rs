@@ -0,0 +1,246 @@
+//! Declarative descriptions of mock sites.
+
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A site with a single user and their courses.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Fixture {
+    #[serde(default)]
+    pub site: Site,
+    pub user: User,
+    #[serde(default)]
+    pub courses: Vec<Course>,
+    /// Errors returned instead of calling a web service function, by function
+    /// name.
+    #[serde(default)]
+    pub errors: BTreeMap<String, WsError>,
+    /// Recorded responses, which are returned for matching requests instead
+    /// of responses generated from the fixture.
+    #[serde(default)]
+    pub recordings: Vec<Recording>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Site {
+    pub name: String,
+    /// The release, such as `4.1.2 (Build: 20230313)`.
+    pub release: String,
+    /// The version number, such as `2022112802`.
+    pub version: u64,
+    pub web_services: bool,
+    pub mobile_service: bool,
+    pub maintenance: bool,
+    pub can_download_files: bool,
+    /// The functions available to the token, or `None` for all implemented
+    /// functions.
+    pub functions: Option<Vec<String>>,
+}
+
+impl Default for Site {
+    fn default() -> Self {
+        Self {
+            name: "Mock Moodle".to_string(),
+            release: "4.1.2 (Build: 20230313)".to_string(),
+            version: 2_022_112_802,
+            web_services: true,
+            mobile_service: true,
+            maintenance: false,
+            can_download_files: true,
+            functions: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct User {
+    pub id: u64,
+    pub username: String,
+    pub password: String,
+    pub first_name: String,
+    pub last_name: String,
+    /// The token as 32 hexadecimal digits.
+    pub token: String,
+    #[serde(default)]
+    pub private_token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Course {
+    pub id: u64,
+    pub short_name: String,
+    pub full_name: String,
+    #[serde(default)]
+    pub category: Option<u64>,
+    #[serde(default)]
+    pub sections: Vec<Section>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Section {
+    pub id: u64,
+    pub name: String,
+    /// The summary as HTML.
+    #[serde(default)]
+    pub summary: String,
+    #[serde(default)]
+    pub modules: Vec<Module>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Module {
+    pub id: u64,
+    pub name: String,
+    /// The module type, such as `resource` or `folder`.
+    #[serde(default = "default_mod_name")]
+    pub mod_name: String,
+    #[serde(default)]
+    pub files: Vec<File>,
+}
+
+fn default_mod_name() -> String {
+    "resource".to_string()
+}
+
+/// A file of a module, served via `webservice/pluginfile.php`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct File {
+    pub name: String,
+    pub content: String,
+    /// The modification time as a Unix timestamp.
+    pub modified: i64,
+}
+
+/// A web service error, such as
+/// `{"exception": "moodle_exception", "errorcode": "sitemaintenance", ...}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WsError {
+    #[serde(default = "default_exception")]
+    pub exception: String,
+    pub errorcode: String,
+    pub message: String,
+}
+
+fn default_exception() -> String {
+    "moodle_exception".to_string()
+}
+
+impl WsError {
+    #[must_use]
+    pub fn new(errorcode: &str, message: &str) -> Self {
+        Self {
+            exception: default_exception(),
+            errorcode: errorcode.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// A request and its response as written by `edu_ws::record`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub function: String,
+    pub params: Vec<(String, String)>,
+    pub response: Value,
+}
+
+impl Recording {
+    /// Reads all recordings in `dir` and its subdirectories.
+    pub fn read_dir(dir: &Path) -> io::Result<Vec<Self>> {
+        let mut recordings = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                recordings.extend(Self::read_dir(&path)?);
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                let json = fs::read_to_string(&path)?;
+                let recording = serde_json::from_str(&json)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                recordings.push(recording);
+            }
+        }
+        Ok(recordings)
+    }
+
+    /// Whether this is a recording of calling `function` with `params`,
+    /// ignoring settings such as the language.
+    pub(crate) fn matches(&self, function: &str, params: &[(String, String)]) -> bool {
+        fn selecting(params: &[(String, String)]) -> Vec<&(String, String)> {
+            let mut params = params
+                .iter()
+                .filter(|(name, _)| !name.starts_with("moodlewssetting"))
+                .collect::<Vec<_>>();
+            params.sort_unstable();
+            params
+        }
+
+        self.function == function && selecting(&self.params) == selecting(params)
+    }
+}
+
+impl Fixture {
+    /// A student enrolled in two courses with a few files.
+    ///
+    /// The fixture is read from `fixtures/example.json`.
+    #[must_use]
+    pub fn example() -> Self {
+        serde_json::from_str(include_str!("../fixtures/example.json")).unwrap()
+    }
+
+    #[must_use]
+    pub fn course_mut(&mut self, id: u64) -> Option<&mut Course> {
+        self.courses.iter_mut().find(|course| course.id == id)
+    }
+
+    pub(crate) fn module(&self, id: u64) -> Option<&Module> {
+        self.courses
+            .iter()
+            .flat_map(|course| &course.sections)
+            .flat_map(|section| &section.modules)
+            .find(|module| module.id == id)
+    }
+}
+
+impl Course {
+    /// Looks up a file by its module id and name.
+    #[must_use]
+    pub fn file_mut(&mut self, module_id: u64, name: &str) -> Option<&mut File> {
+        self.sections
+            .iter_mut()
+            .flat_map(|section| &mut section.modules)
+            .filter(|module| module.id == module_id)
+            .flat_map(|module| &mut module.files)
+            .find(|file| file.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_fixture() {
+        let mut fixture = Fixture::example();
+        assert_eq!(fixture.site, Site::default());
+        assert_eq!(fixture.courses.len(), 2);
+        assert_eq!(fixture.module(10).unwrap().mod_name, "resource");
+        let file = fixture
+            .course_mut(42)
+            .unwrap()
+            .file_mut(10, "notes.pdf")
+            .unwrap();
+        assert_eq!(file.content, "Lecture notes, version 1");
+    }
+}
@@ -0,0 +1,427 @@
+//! Responses of the mock endpoints.
+
+use std::sync::{Mutex, RwLock};
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::{header, Response, StatusCode};
+use percent_encoding::percent_decode_str;
+use serde_json::{json, Value};
+use url::Url;
+
+use crate::fixture::{Fixture, Module, WsError};
+
+/// The functions implemented by the mock.
+pub const FUNCTIONS: [&str; 5] = [
+    "core_webservice_get_site_info",
+    "core_enrol_get_users_courses",
+    "core_course_get_categories",
+    "core_course_get_contents",
+    "tool_mobile_call_external_functions",
+];
+
+const BATCH_FUNCTION: &str = "tool_mobile_call_external_functions";
+
+#[derive(Debug)]
+pub struct State {
+    url: Url,
+    pub fixture: RwLock<Fixture>,
+    pub calls: Mutex<Vec<String>>,
+}
+
+/// The parameters of a web service function, either from a form or from the
+/// JSON arguments of a batched call.
+enum Params<'a> {
+    Form(&'a [(String, String)]),
+    Json(&'a Value),
+}
+
+impl Params<'_> {
+    fn get(&self, key: &str) -> Option<String> {
+        match self {
+            Self::Form(form) => form
+                .iter()
+                .find(|(form_key, _)| form_key == key)
+                .map(|(_, value)| value.clone()),
+            Self::Json(value) => match value.get(key)? {
+                Value::String(string) => Some(string.clone()),
+                value => Some(value.to_string()),
+            },
+        }
+    }
+
+    fn get_u64(&self, key: &str) -> Result<u64, WsError> {
+        self.get(key)
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| invalid_parameter(key))
+    }
+}
+
+impl State {
+    pub fn new(url: Url, fixture: Fixture) -> Self {
+        Self {
+            url,
+            fixture: RwLock::new(fixture),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn respond(&self, path: &str, query: &str, body: &[u8]) -> Response<Full<Bytes>> {
+        let mut params = form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect::<Vec<_>>();
+        let fixture = self.fixture.read().unwrap();
+        match path {
+            "/login/token.php" => {
+                params.extend(form_urlencoded::parse(body).into_owned());
+                json_response(&self.login(&fixture, &Params::Form(&params)))
+            }
+            "/webservice/rest/server.php" => {
+                let form = form_urlencoded::parse(body)
+                    .into_owned()
+                    .collect::<Vec<_>>();
+                let query = Params::Form(&params);
+                let function = query.get("wsfunction").unwrap_or_default();
+                self.calls.lock().unwrap().push(function.clone());
+                let response = if query.get("wstoken").as_ref() == Some(&fixture.user.token) {
+                    self.call(&fixture, &function, &Params::Form(&form))
+                } else {
+                    Err(WsError::new(
+                        "invalidtoken",
+                        "Invalid token - token not found",
+                    ))
+                };
+                json_response(&response.unwrap_or_else(|err| json!(err)))
+            }
+            "/lib/ajax/service-nologin.php" => {
+                let requests = serde_json::from_slice::<Vec<Value>>(body).unwrap_or_default();
+                let responses = requests
+                    .iter()
+                    .map(|request| match request["methodname"].as_str() {
+                        Some("tool_mobile_get_public_config") => {
+                            json!({ "error": false, "data": self.public_config(&fixture) })
+                        }
+                        _ => json!({
+                            "error": true,
+                            "exception": WsError::new(
+                                "servicenotavailable",
+                                "Web service is not available",
+                            ),
+                        }),
+                    })
+                    .collect::<Vec<_>>();
+                json_response(&Value::Array(responses))
+            }
+            _ => match path.strip_prefix("/webservice/pluginfile.php/") {
+                Some(file_path) => {
+                    let token = Params::Form(&params).get("token");
+                    if token.as_ref() == Some(&fixture.user.token) {
+                        self.file(&fixture, file_path)
+                    } else {
+                        status_response(StatusCode::FORBIDDEN)
+                    }
+                }
+                None => status_response(StatusCode::NOT_FOUND),
+            },
+        }
+    }
+
+    fn login(&self, fixture: &Fixture, params: &Params<'_>) -> Value {
+        let (errorcode, error) = if !fixture.site.web_services || !fixture.site.mobile_service {
+            (
+                "enablewsdescription",
+                "Web services must be enabled in Advanced features.",
+            )
+        } else if params.get("username").as_ref() == Some(&fixture.user.username)
+            && params.get("password").as_ref() == Some(&fixture.user.password)
+        {
+            return json!({
+                "token": fixture.user.token,
+                "privatetoken": fixture.user.private_token,
+            });
+        } else {
+            ("invalidlogin", "Invalid login, please try again")
+        };
+        json!({
+            "error": error,
+            "errorcode": errorcode,
+            "stacktrace": "",
+            "debuginfo": "",
+            "reproductionlink": self.url,
+        })
+    }
+
+    fn call(
+        &self,
+        fixture: &Fixture,
+        function: &str,
+        params: &Params<'_>,
+    ) -> Result<Value, WsError> {
+        if fixture.site.maintenance {
+            return Err(WsError::new(
+                "sitemaintenance",
+                "Site is in maintenance mode",
+            ));
+        }
+        if let Some(err) = fixture.errors.get(function) {
+            return Err(err.clone());
+        }
+        if let Params::Form(form) = params {
+            if let Some(recording) = fixture
+                .recordings
+                .iter()
+                .find(|recording| recording.matches(function, form))
+            {
+                return Ok(recording.response.clone());
+            }
+        }
+        if !FUNCTIONS.contains(&function)
+            || fixture
+                .site
+                .functions
+                .as_ref()
+                .is_some_and(|functions| !functions.iter().any(|name| name == function))
+        {
+            return Err(WsError {
+                exception: "webservice_access_exception".to_string(),
+                errorcode: "accessexception".to_string(),
+                message: "Access control exception".to_string(),
+            });
+        }
+
+        match function {
+            "core_webservice_get_site_info" => Ok(self.site_info(fixture)),
+            "core_enrol_get_users_courses" => {
+                params.get_u64("userid")?;
+                let courses = fixture
+                    .courses
+                    .iter()
+                    .map(|course| {
+                        json!({
+                            "id": course.id,
+                            "shortname": course.short_name,
+                            "fullname": course.full_name,
+                            "displayname": course.full_name,
+                            "idnumber": "",
+                            "visible": 1,
+                            "category": course.category,
+                        })
+                    })
+                    .collect();
+                Ok(Value::Array(courses))
+            }
+            "core_course_get_categories" => Ok(json!([])),
+            "core_course_get_contents" => {
+                let course_id = params.get_u64("courseid")?;
+                let course = fixture
+                    .courses
+                    .iter()
+                    .find(|course| course.id == course_id)
+                    .ok_or_else(|| WsError {
+                        exception: "dml_missing_record_exception".to_string(),
+                        errorcode: "invalidrecord".to_string(),
+                        message: "Can't find data record in database table course.".to_string(),
+                    })?;
+                let sections = course
+                    .sections
+                    .iter()
+                    .enumerate()
+                    .map(|(number, section)| {
+                        let modules = section
+                            .modules
+                            .iter()
+                            .map(|module| self.module(module))
+                            .collect::<Vec<_>>();
+                        json!({
+                            "id": section.id,
+                            "name": section.name,
+                            "visible": 1,
+                            "summary": section.summary,
+                            "summaryformat": 1,
+                            "section": number,
+                            "uservisible": true,
+                            "modules": modules,
+                        })
+                    })
+                    .collect();
+                Ok(Value::Array(sections))
+            }
+            BATCH_FUNCTION => {
+                let responses = (0..)
+                    .map_while(|index| {
+                        let function = params.get(&format!("requests[{index}][function]"))?;
+                        let arguments = params
+                            .get(&format!("requests[{index}][arguments]"))
+                            .unwrap_or_default();
+                        Some((function, arguments))
+                    })
+                    .map(|(function, arguments)| {
+                        let arguments = serde_json::from_str(&arguments).unwrap_or(Value::Null);
+                        let result = if function == BATCH_FUNCTION {
+                            Err(invalid_parameter("function"))
+                        } else {
+                            self.call(fixture, &function, &Params::Json(&arguments))
+                        };
+                        match result {
+                            Ok(data) => json!({ "error": false, "data": data.to_string() }),
+                            Err(err) => json!({
+                                "error": true,
+                                "exception": json!(err).to_string(),
+                            }),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                Ok(json!({ "responses": responses }))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn site_info(&self, fixture: &Fixture) -> Value {
+        let site = &fixture.site;
+        let user = &fixture.user;
+        let functions = site
+            .functions
+            .as_ref()
+            .map_or_else(|| FUNCTIONS.map(str::to_string).to_vec(), Clone::clone);
+        let functions = functions
+            .iter()
+            .map(|name| json!({ "name": name, "version": site.version.to_string() }))
+            .collect::<Vec<_>>();
+        json!({
+            "sitename": site.name,
+            "username": user.username,
+            "firstname": user.first_name,
+            "lastname": user.last_name,
+            "fullname": format!("{} {}", user.first_name, user.last_name),
+            "lang": "en",
+            "userid": user.id,
+            "siteurl": self.url,
+            "userpictureurl": self.url.join("theme/image.php/boost/core/1/u/f1").unwrap(),
+            "functions": functions,
+            "downloadfiles": u8::from(site.can_download_files),
+            "release": site.release,
+            "version": format!("{}.00", site.version),
+            "advancedfeatures": [
+                { "name": "enablemobilewebservice", "value": u8::from(site.mobile_service) },
+            ],
+        })
+    }
+
+    fn public_config(&self, fixture: &Fixture) -> Value {
+        let site = &fixture.site;
+        json!({
+            "wwwroot": self.url,
+            "httpswwwroot": self.url,
+            "sitename": site.name,
+            "guestlogin": 0,
+            "rememberusername": 1,
+            "authloginviaemail": 0,
+            "registerauth": "",
+            "forgottenpasswordurl": "",
+            "authinstructions": "",
+            "authnoneenabled": 0,
+            "enablewebservices": u8::from(site.web_services),
+            "enablemobilewebservice": u8::from(site.mobile_service),
+            "maintenanceenabled": u8::from(site.maintenance),
+            "maintenancemessage": "",
+            "typeoflogin": 1,
+            "launchurl": self.url.join("admin/tool/mobile/launch.php").unwrap(),
+            "identityproviders": [],
+            "warnings": [],
+        })
+    }
+
+    fn module(&self, module: &Module) -> Value {
+        let contents = module
+            .files
+            .iter()
+            .map(|file| {
+                let mut url = self.url.join("webservice/pluginfile.php").unwrap();
+                url.path_segments_mut().unwrap().extend([
+                    &module.id.to_string(),
+                    &format!("mod_{}", module.mod_name),
+                    "content",
+                    "0",
+                    &file.name,
+                ]);
+                url.query_pairs_mut().append_pair("forcedownload", "1");
+                json!({
+                    "type": "file",
+                    "filename": file.name,
+                    "filepath": "/",
+                    "filesize": file.content.len(),
+                    "fileurl": url,
+                    "timecreated": file.modified,
+                    "timemodified": file.modified,
+                    "sortorder": 0,
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut url = self
+            .url
+            .join(&format!("mod/{}/view.php", module.mod_name))
+            .unwrap();
+        url.query_pairs_mut()
+            .append_pair("id", &module.id.to_string());
+        json!({
+            "id": module.id,
+            "url": url,
+            "name": module.name,
+            "instance": module.id,
+            "visible": 1,
+            "uservisible": true,
+            "modicon": self.url.join(&format!("theme/image.php/boost/{}/1/icon", module.mod_name)).unwrap(),
+            "modname": module.mod_name,
+            "modplural": format!("{}s", module.mod_name),
+            "indent": 0,
+            "contents": contents,
+        })
+    }
+
+    /// Serves `{module id}/{component}/{area}/{revision}/{file name}`.
+    fn file(&self, fixture: &Fixture, path: &str) -> Response<Full<Bytes>> {
+        let segments = path
+            .split('/')
+            .map(|segment| percent_decode_str(segment).decode_utf8_lossy())
+            .collect::<Vec<_>>();
+        let file = match segments.as_slice() {
+            [module_id, _component, _area, _revision, name] => module_id
+                .parse()
+                .ok()
+                .and_then(|module_id| fixture.module(module_id))
+                .and_then(|module| module.files.iter().find(|file| file.name == *name)),
+            _ => None,
+        };
+        match file {
+            Some(file) if fixture.site.can_download_files => Response::builder()
+                .header(header::CONTENT_TYPE, "application/octet-stream")
+                .body(Full::new(Bytes::from(file.content.clone())))
+                .unwrap(),
+            Some(_) => status_response(StatusCode::FORBIDDEN),
+            None => status_response(StatusCode::NOT_FOUND),
+        }
+    }
+}
+
+fn invalid_parameter(key: &str) -> WsError {
+    WsError {
+        exception: "invalid_parameter_exception".to_string(),
+        errorcode: "invalidparameter".to_string(),
+        message: format!("Invalid parameter value detected ({key})"),
+    }
+}
+
+fn json_response(value: &Value) -> Response<Full<Bytes>> {
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(value.to_string())))
+        .unwrap()
+}
+
+fn status_response(status: StatusCode) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .body(Full::default())
+        .unwrap()
+}
@@ -0,0 +1,112 @@
+//! A mock Moodle server for testing edu-ws.
+//!
+//! [`MockServer`] serves a [`Fixture`] on a local port, implementing
+//! `login/token.php`, `webservice/rest/server.php`,
+//! `lib/ajax/service-nologin.php` and `webservice/pluginfile.php` well enough
+//! to add an account, fetch its courses and sync them.
+
+#![warn(rust_2018_idioms)]
+#![warn(clippy::default_trait_access)]
+#![warn(clippy::inconsistent_struct_constructor)]
+#![warn(clippy::semicolon_if_nothing_returned)]
+#![deny(rustdoc::all)]
+
+pub mod fixture;
+mod handler;
+
+use std::{convert::Infallible, io, net::Ipv4Addr, sync::Arc};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{body::Incoming, server::conn::http1, service::service_fn, Request, Response};
+use hyper_util::rt::TokioIo;
+use tokio::{net::TcpListener, task::JoinHandle};
+use url::Url;
+
+use crate::handler::State;
+pub use crate::{fixture::Fixture, handler::FUNCTIONS};
+
+/// A running mock server.
+///
+/// The server stops when dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    url: Url,
+    state: Arc<State>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Starts serving `fixture` on a free local port.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub async fn start(fixture: Fixture) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let url = Url::parse(&format!("http://{}/", listener.local_addr()?)).unwrap();
+        let state = Arc::new(State::new(url.clone(), fixture));
+        let task = tokio::spawn(serve(listener, state.clone()));
+        Ok(Self { url, state, task })
+    }
+
+    /// The site URL.
+    #[must_use]
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// A copy of the served fixture.
+    #[must_use]
+    pub fn fixture(&self) -> Fixture {
+        self.state.fixture.read().unwrap().clone()
+    }
+
+    /// Changes the served fixture, such as to modify files between syncs.
+    pub fn update(&self, f: impl FnOnce(&mut Fixture)) {
+        f(&mut self.state.fixture.write().unwrap());
+    }
+
+    /// The names of the web service functions called so far, in order.
+    ///
+    /// Functions called via `tool_mobile_call_external_functions` are not
+    /// listed separately.
+    #[must_use]
+    pub fn calls(&self) -> Vec<String> {
+        self.state.calls.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve(listener: TcpListener, state: Arc<State>) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let state = state.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request| handle(state.clone(), request));
+            // Clients may close connections at any time.
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
+
+async fn handle(
+    state: Arc<State>,
+    request: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let (parts, body) = request.into_parts();
+    let body = body
+        .collect()
+        .await
+        .map(|collected| collected.to_bytes())
+        .unwrap_or_default();
+    let query = parts.uri.query().unwrap_or_default();
+    Ok(state.respond(parts.uri.path(), query, &body))
+}
@@ -6,7 +6,14 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 use url::Url;
 
-use crate::{response::config::Config, serde::UntaggedResultHelper};
+use crate::{
+    response::config::Config, serde::UntaggedResultHelper, token::login::Response as TokenResponse,
+};
+
+/// A User-Agent identifying as the Moodle app.
+///
+/// Moodle restricts some functions to requests from its app.
+const APP_USER_AGENT: &str = concat!("MoodleMobile edu-ws/", env!("CARGO_PKG_VERSION"));
 
 #[derive(Debug)]
 pub struct Client {
@@ -26,6 +33,21 @@ pub enum Exception {
         #[serde(rename = "moreinfourl")]
         info_url: Url,
     },
+    #[error("{message}")]
+    #[serde(rename = "qrcodedisabled")]
+    QrCodeDisabled { message: String },
+    #[error("{message}")]
+    #[serde(rename = "apprequired")]
+    AppRequired { message: String },
+    #[error("{message}")]
+    #[serde(rename = "invalidkey")]
+    InvalidKey { message: String },
+    #[error("{message}")]
+    #[serde(rename = "expiredkey")]
+    ExpiredKey { message: String },
+    #[error("{message}")]
+    #[serde(rename = "ipmismatch")]
+    IpMismatch { message: String },
 }
 
 #[derive(Deserialize, Debug, PartialEq)]
@@ -45,11 +67,11 @@ impl<T> From<AjaxResult<T>> for Result<T, Exception> {
 }
 
 #[derive(Serialize)]
-struct Request<'a> {
+struct Request<'a, A> {
     #[serde(rename = "methodname")]
     method: &'a str,
     #[serde(rename = "args")]
-    arguments: HashMap<String, String>,
+    arguments: A,
 }
 
 impl Client {
@@ -62,16 +84,20 @@ impl Client {
         }
     }
 
-    async fn call_ajax<T>(
+    async fn call_ajax<A, T>(
         &self,
-        requests: &[Request<'_>],
+        requests: &[Request<'_, A>],
+        user_agent: Option<&str>,
     ) -> Result<Vec<Result<T, Exception>>, ReceiveError>
     where
+        A: Serialize,
         T: DeserializeOwned,
     {
-        let res = self
-            .http_client
-            .post(self.ajax_url.clone())
+        let mut request = self.http_client.post(self.ajax_url.clone());
+        if let Some(user_agent) = user_agent {
+            request = request.header(reqwest::header::USER_AGENT, user_agent);
+        }
+        let res = request
             .json(&requests)
             .send()
             .await?
@@ -86,10 +112,13 @@ impl Client {
 
     pub async fn get_config(&self) -> Result<Config, Error> {
         let config = self
-            .call_ajax(&[Request {
-                method: "tool_mobile_get_public_config",
-                arguments: HashMap::new(),
-            }])
+            .call_ajax(
+                &[Request {
+                    method: "tool_mobile_get_public_config",
+                    arguments: HashMap::<String, String>::new(),
+                }],
+                None,
+            )
             .await?
             .into_iter()
             .next()
@@ -97,6 +126,42 @@ impl Client {
 
         Ok(config)
     }
+
+    /// Exchanges a QR login key for a token.
+    ///
+    /// Moodle only accepts the key from the IP address that displayed the QR
+    /// code, shortly after it was created.
+    pub async fn get_tokens_for_qr_login(
+        &self,
+        qr_login_key: &str,
+        user_id: u64,
+    ) -> Result<TokenResponse, Error> {
+        #[derive(Serialize)]
+        struct Arguments<'a> {
+            #[serde(rename = "qrloginkey")]
+            qr_login_key: &'a str,
+            #[serde(rename = "userid")]
+            user_id: u64,
+        }
+
+        let response = self
+            .call_ajax(
+                &[Request {
+                    method: "tool_mobile_get_tokens_for_qr_login",
+                    arguments: Arguments {
+                        qr_login_key,
+                        user_id,
+                    },
+                }],
+                Some(APP_USER_AGENT),
+            )
+            .await?
+            .into_iter()
+            .next()
+            .unwrap()?;
+
+        Ok(response)
+    }
 }
 
 #[derive(Error, Deserialize, Debug, PartialEq)]
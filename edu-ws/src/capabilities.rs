@@ -0,0 +1,119 @@
+//! The capabilities of a site, as reported by its site info.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::response::info::Info;
+
+/// Moodle 3.5.3, which introduced `includestealthmodules` for
+/// `core_course_get_contents`.
+pub const MOODLE_3_5_3: u64 = 2_018_051_703;
+
+/// Moodle 3.7, which introduced `returnusercount` for
+/// `core_enrol_get_users_courses`.
+pub const MOODLE_3_7: u64 = 2_019_052_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct SiteCapabilities {
+    /// The functions available to the token.
+    pub functions: BTreeSet<String>,
+    /// The release, such as `4.1.2 (Build: 20230313)`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release: Option<String>,
+    /// The version number, such as `2022112802`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    /// The advanced features by name and whether they are enabled.
+    #[serde(default)]
+    pub advanced_features: BTreeMap<String, bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub can_download_files: Option<bool>,
+}
+
+impl From<&Info> for SiteCapabilities {
+    fn from(info: &Info) -> Self {
+        Self {
+            functions: info
+                .functions
+                .iter()
+                .map(|function| function.name.clone())
+                .collect(),
+            release: info.release.clone(),
+            version: info.version.as_deref().and_then(parse_version),
+            advanced_features: info
+                .advanced_features
+                .iter()
+                .map(|feature| (feature.name.clone(), feature.enabled))
+                .collect(),
+            can_download_files: info.can_download_files,
+        }
+    }
+}
+
+impl SiteCapabilities {
+    /// Whether the token may call `function`.
+    #[must_use]
+    pub fn supports(&self, function: &str) -> bool {
+        self.functions.contains(function)
+    }
+
+    /// Whether the site runs at least the Moodle `version`, if known.
+    #[must_use]
+    pub fn is_at_least(&self, version: u64) -> Option<bool> {
+        self.version.map(|site_version| site_version >= version)
+    }
+
+    /// Whether an advanced feature such as `enablemobilewebservice` is
+    /// enabled, if known.
+    #[must_use]
+    pub fn feature(&self, name: &str) -> Option<bool> {
+        self.advanced_features.get(name).copied()
+    }
+}
+
+/// Parses the integral part of a version such as `2022112802.00`.
+fn parse_version(version: &str) -> Option<u64> {
+    version.split('.').next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_site_capabilities() -> serde_json::Result<()> {
+        let info = serde_json::from_value::<Info>(json!({
+            "sitename": "site_name",
+            "username": "username",
+            "firstname": "first_name",
+            "lastname": "last_name",
+            "fullname": "full_name",
+            "lang": "en",
+            "userid": 1,
+            "siteurl": "https://example.com",
+            "userpictureurl": "https://example.com/user_picture",
+            "functions": [
+                { "name": "core_course_get_contents", "version": "2022112800" },
+            ],
+            "downloadfiles": 1,
+            "release": "4.1.2 (Build: 20230313)",
+            "version": "2022112802.00",
+            "advancedfeatures": [
+                { "name": "enablemobilewebservice", "value": 1 },
+            ],
+        }))?;
+        let capabilities = SiteCapabilities::from(&info);
+        assert!(capabilities.supports("core_course_get_contents"));
+        assert!(!capabilities.supports("mod_forum_get_forums_by_courses"));
+        assert_eq!(capabilities.version, Some(2_022_112_802));
+        assert_eq!(capabilities.is_at_least(MOODLE_3_7), Some(true));
+        assert_eq!(capabilities.feature("enablemobilewebservice"), Some(true));
+        assert_eq!(capabilities.feature("enablebadges"), None);
+        assert_eq!(capabilities.can_download_files, Some(true));
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+//! Typed web service functions.
+//!
+//! Each function is represented by its parameters. Call them with
+//! [`Client::call`](crate::ws::Client::call).
+
+use serde::Serialize;
+use serde_with::serde_as;
+
+use crate::{
+    response::{
+        category::Category,
+        content::Section,
+        course::Course,
+        external::ExternalResponses,
+        forum::{Discussions, Forum, Posts},
+        grade::GradeItems,
+        info::Info,
+        WithWarnings,
+    },
+    serde::NumBool,
+    ws::WsFunction,
+};
+
+#[derive(Serialize, Debug, WsFunction)]
+#[ws_function(name = "core_webservice_get_site_info", response = Info)]
+pub struct GetSiteInfo;
+
+#[serde_as]
+#[derive(Serialize, Debug, WsFunction)]
+#[ws_function(name = "core_enrol_get_users_courses", response = Vec<Course>)]
+pub struct GetUsersCourses {
+    #[serde(rename = "userid")]
+    pub user_id: u64,
+    /// Requires Moodle 3.7 or later.
+    #[serde_as(as = "Option<NumBool>")]
+    #[serde(rename = "returnusercount")]
+    pub return_user_count: Option<bool>,
+}
+
+/// Returns all course categories visible to the user.
+#[derive(Serialize, Debug, WsFunction)]
+#[ws_function(name = "core_course_get_categories", response = Vec<Category>)]
+pub struct GetCategories;
+
+#[derive(Serialize, Debug, WsFunction)]
+#[ws_function(name = "core_course_get_contents", response = Vec<Section>)]
+pub struct GetContents {
+    #[serde(rename = "courseid")]
+    pub course_id: u64,
+    pub options: Vec<ContentsOption>,
+}
+
+/// An option of `core_course_get_contents`, serialized as `name` and `value`.
+#[derive(Serialize, Debug)]
+#[serde(tag = "name", content = "value", rename_all = "lowercase")]
+pub enum ContentsOption {
+    ExcludeModules(bool),
+    ExcludeContents(bool),
+    /// Requires Moodle 3.5.3 or later.
+    IncludeStealthModules(bool),
+    SectionId(u64),
+    SectionNumber(u64),
+    #[serde(rename = "cmid")]
+    CourseModuleId(u64),
+    ModName(String),
+    ModId(u64),
+}
+
+#[derive(Serialize, Debug, WsFunction)]
+#[ws_function(name = "mod_forum_get_forums_by_courses", response = Vec<Forum>)]
+pub struct GetForumsByCourses {
+    #[serde(rename = "courseids")]
+    pub course_ids: Vec<u64>,
+}
+
+/// Requires Moodle 3.7 or later.
+#[derive(Serialize, Debug, WsFunction)]
+#[ws_function(name = "mod_forum_get_forum_discussions", response = WithWarnings<Discussions>)]
+pub struct GetForumDiscussions {
+    #[serde(rename = "forumid")]
+    pub forum_id: u64,
+}
+
+/// Requires Moodle 3.7 or later.
+#[derive(Serialize, Debug, WsFunction)]
+#[ws_function(name = "mod_forum_get_discussion_posts", response = WithWarnings<Posts>)]
+pub struct GetDiscussionPosts<'a> {
+    #[serde(rename = "discussionid")]
+    pub discussion_id: u64,
+    /// `ASC` or `DESC`.
+    #[serde(rename = "sortdirection")]
+    pub sort_direction: &'a str,
+}
+
+#[derive(Serialize, Debug, WsFunction)]
+#[ws_function(name = "gradereport_user_get_grade_items", response = WithWarnings<GradeItems>)]
+pub struct GetGradeItems {
+    #[serde(rename = "courseid")]
+    pub course_id: u64,
+    #[serde(rename = "userid")]
+    pub user_id: u64,
+}
+
+/// Calls several functions in a single request.
+///
+/// See [`Client::call_batch`](crate::ws::Client::call_batch).
+#[derive(Serialize, Debug, WsFunction)]
+#[ws_function(name = "tool_mobile_call_external_functions", response = ExternalResponses)]
+pub struct CallExternalFunctions<'a> {
+    pub requests: Vec<ExternalRequest<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ExternalRequest<'a> {
+    pub function: &'a str,
+    /// The JSON-encoded parameters.
+    pub arguments: String,
+    #[serde(rename = "settingfilter")]
+    pub filter: bool,
+    #[serde(rename = "settinglang")]
+    pub lang: Option<&'a str>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serde::form::to_form;
+
+    #[test]
+    fn test_ws_function_derive() {
+        #[derive(Serialize, Debug, WsFunction)]
+        #[ws_function(
+            name = "core_user_get_users_by_field",
+            response = Vec<serde_json::Value>
+        )]
+        struct GetUsersByField<'a> {
+            field: &'a str,
+        }
+
+        assert_eq!(GetUsersByField::NAME, "core_user_get_users_by_field");
+        assert_eq!(GetSiteInfo::NAME, "core_webservice_get_site_info");
+        assert_eq!(GetContents::NAME, "core_course_get_contents");
+    }
+
+    #[test]
+    fn test_get_contents_serialization() {
+        let params = GetContents {
+            course_id: 2,
+            options: vec![
+                ContentsOption::IncludeStealthModules(true),
+                ContentsOption::CourseModuleId(3),
+            ],
+        };
+        assert_eq!(
+            to_form(&params).unwrap(),
+            [
+                ("courseid", "2"),
+                ("options[0][name]", "includestealthmodules"),
+                ("options[0][value]", "1"),
+                ("options[1][name]", "cmid"),
+                ("options[1][value]", "3"),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+        );
+    }
+}
@@ -6,8 +6,13 @@
 #![warn(clippy::semicolon_if_nothing_returned)]
 #![deny(rustdoc::all)]
 
+extern crate self as edu_ws;
+
 pub mod ajax;
+pub mod capabilities;
+pub mod function;
+pub mod record;
 pub mod response;
-mod serde;
+pub mod serde;
 pub mod token;
 pub mod ws;
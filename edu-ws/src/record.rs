@@ -0,0 +1,373 @@
+//! Recording of web service responses as test fixtures.
+//!
+//! A [`Recorder`] attached to a [`Client`](crate::ws::Client) writes each
+//! request and its response to a directory as a [`Recording`]. Tokens, names
+//! and other personal data are redacted and the site URL is replaced with
+//! `https://example.com`, so that recordings can be shared. Recordings are
+//! replayed by `edu-ws-mock` and decoded by the corpus check of this crate.
+//!
+//! File operations block.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::{
+    function::CallExternalFunctions,
+    ws::{self, WsFunction},
+};
+
+/// The URL replacing the site URL in recordings.
+pub const REDACTED_SITE_URL: &str = "https://example.com";
+
+const REDACTED: &str = "redacted";
+
+/// Keys whose string values are personal data.
+const PERSONAL_KEYS: [&str; 8] = [
+    "username",
+    "firstname",
+    "lastname",
+    "email",
+    "userfullname",
+    "author",
+    "authorfullname",
+    "usermodifiedfullname",
+];
+
+/// Keys whose values are URLs to pictures of users.
+const PICTURE_KEYS: [&str; 4] = [
+    "userpictureurl",
+    "profileimageurl",
+    "profileimageurlsmall",
+    "userpictureurlsmall",
+];
+
+/// Parameters that do not select the response, such as the language.
+const SETTING_PREFIX: &str = "moodlewssetting";
+
+/// A request and its response.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub function: String,
+    /// The form parameters.
+    pub params: Vec<(String, String)>,
+    pub response: Value,
+}
+
+impl Recording {
+    /// Creates a recording of `response`, redacting personal data and
+    /// `site_url`.
+    pub fn new(
+        function: &str,
+        params: Vec<(String, String)>,
+        response: &str,
+        site_url: &Url,
+    ) -> serde_json::Result<Self> {
+        let site_url = site_url.as_str().trim_end_matches('/');
+        let mut response = serde_json::from_str(response)?;
+        redact(&mut response, site_url);
+        if function == CallExternalFunctions::NAME {
+            redact_external_responses(&mut response, site_url)?;
+        }
+        let params = params
+            .into_iter()
+            .map(|(key, value)| (key, value.replace(site_url, REDACTED_SITE_URL)))
+            .collect();
+        Ok(Self {
+            function: function.to_string(),
+            params,
+            response,
+        })
+    }
+
+    /// The file name, which is unique for the function and parameters.
+    ///
+    /// Recordings that [`matches`](Self::matches) the same call share it.
+    #[must_use]
+    pub fn file_name(&self) -> String {
+        let mut key = self.function.clone();
+        for (name, value) in selecting_params(&self.params) {
+            key.push_str(&format!("&{name}={value}"));
+        }
+        let digest = format!("{:x}", md5::compute(key));
+        format!("{}-{}.json", self.function, &digest[..12])
+    }
+
+    /// Whether this is a recording of calling `function` with `params`.
+    ///
+    /// Settings such as the language are ignored.
+    #[must_use]
+    pub fn matches(&self, function: &str, params: &[(String, String)]) -> bool {
+        self.function == function && selecting_params(&self.params) == selecting_params(params)
+    }
+
+    /// The value of the parameter `name`.
+    #[must_use]
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param_name, _)| param_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Decodes the response like [`Client`](crate::ws::Client) does.
+    pub fn decode<T: DeserializeOwned>(&self) -> ws::Result<T> {
+        ws::decode(&self.response.to_string())
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Reads all recordings in `dir` and its subdirectories, sorted by path.
+    pub fn read_dir(dir: &Path) -> io::Result<Vec<(PathBuf, Self)>> {
+        fn visit(dir: &Path, recordings: &mut Vec<(PathBuf, Recording)>) -> io::Result<()> {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    visit(&path, recordings)?;
+                } else if path
+                    .extension()
+                    .is_some_and(|extension| extension == "json")
+                {
+                    let recording = Recording::read(&path)?;
+                    recordings.push((path, recording));
+                }
+            }
+            Ok(())
+        }
+
+        let mut recordings = Vec::new();
+        visit(dir, &mut recordings)?;
+        recordings.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(recordings)
+    }
+
+    /// Writes the recording to `dir`, replacing any recording of the same
+    /// request.
+    pub fn write(&self, dir: &Path) -> io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(self.file_name());
+        let json = serde_json::to_string_pretty(self).unwrap();
+        fs::write(&path, json + "\n")?;
+        Ok(path)
+    }
+}
+
+/// Records requests to a directory.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    dir: PathBuf,
+}
+
+impl Recorder {
+    #[must_use]
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Records a response, logging failures instead of returning them.
+    pub(crate) fn record(
+        &self,
+        function: &str,
+        params: &[(String, String)],
+        response: &str,
+        site_url: &Url,
+    ) {
+        let recording = match Recording::new(function, params.to_vec(), response, site_url) {
+            Ok(recording) => recording,
+            Err(err) => {
+                warn!(function, %err, "Could not record response");
+                return;
+            }
+        };
+        match recording.write(&self.dir) {
+            Ok(path) => debug!(function, path = %path.display(), "Recorded response"),
+            Err(err) => warn!(function, %err, "Could not write recording"),
+        }
+    }
+}
+
+/// Redacts personal data and the site URL in `value`.
+/// The parameters that select what a call returns, which excludes settings
+/// such as the language, in a canonical order.
+fn selecting_params(params: &[(String, String)]) -> Vec<&(String, String)> {
+    let mut params = params
+        .iter()
+        .filter(|(name, _)| !name.starts_with(SETTING_PREFIX))
+        .collect::<Vec<_>>();
+    params.sort_unstable();
+    params
+}
+
+fn redact(value: &mut Value, site_url: &str) {
+    match value {
+        Value::String(string) => {
+            if string.contains(site_url) {
+                *string = string.replace(site_url, REDACTED_SITE_URL);
+            }
+            redact_token_params(string);
+        }
+        Value::Array(values) => {
+            for value in values {
+                redact(value, site_url);
+            }
+        }
+        Value::Object(map) => {
+            // The full name of a course, unlike that of a user, is not personal.
+            let is_course = map.contains_key("shortname");
+            for (key, value) in map.iter_mut() {
+                let personal = PERSONAL_KEYS.contains(&key.as_str())
+                    || (key == "fullname" && !is_course)
+                    || key == "token"
+                    || key == "privatetoken";
+                match value {
+                    Value::String(string) if personal => REDACTED.clone_into(string),
+                    Value::String(string) if PICTURE_KEYS.contains(&key.as_str()) => {
+                        *string = format!("{REDACTED_SITE_URL}/user/pix.php");
+                    }
+                    value => redact(value, site_url),
+                }
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+/// Redacts the JSON-encoded data of a batched call.
+fn redact_external_responses(response: &mut Value, site_url: &str) -> serde_json::Result<()> {
+    let Some(responses) = response.get_mut("responses").and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+    for response in responses {
+        for key in ["data", "exception"] {
+            if let Some(Value::String(json)) = response.get_mut(key) {
+                let mut value = serde_json::from_str(json)?;
+                redact(&mut value, site_url);
+                *json = value.to_string();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the values of `token` query parameters, such as in file URLs.
+fn redact_token_params(string: &mut String) {
+    let mut start = 0;
+    while let Some(index) = string[start..].find("token=") {
+        let value_start = start + index + "token=".len();
+        let preceded_by_separator =
+            start + index == 0 || matches!(string.as_bytes()[start + index - 1], b'?' | b'&');
+        let value_end = string[value_start..]
+            .find(['&', '"', '\'', ' ', '#'])
+            .map_or(string.len(), |end| value_start + end);
+        if preceded_by_separator && string[value_start..value_end] != *REDACTED {
+            string.replace_range(value_start..value_end, REDACTED);
+        }
+        start = value_start;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_recording_redaction() {
+        let site_url = "https://moodle.example.edu/".parse().unwrap();
+        let response = json!({
+            "sitename": "Example University",
+            "username": "jdoe",
+            "fullname": "Jane Doe",
+            "siteurl": "https://moodle.example.edu",
+            "userpictureurl": "https://moodle.example.edu/pluginfile.php/5/user/icon/f1",
+            "courses": [{
+                "shortname": "LA",
+                "fullname": "Linear Algebra",
+                "fileurl": "https://moodle.example.edu/webservice/pluginfile.php/1/a.pdf?token=0123&forcedownload=1",
+            }],
+        });
+        let recording = Recording::new(
+            "core_webservice_get_site_info",
+            vec![("moodlewssettinglang".to_string(), "en".to_string())],
+            &response.to_string(),
+            &site_url,
+        )
+        .unwrap();
+        assert_eq!(
+            recording.response,
+            json!({
+                "sitename": "Example University",
+                "username": "redacted",
+                "fullname": "redacted",
+                "siteurl": "https://example.com",
+                "userpictureurl": "https://example.com/user/pix.php",
+                "courses": [{
+                    "shortname": "LA",
+                    "fullname": "Linear Algebra",
+                    "fileurl": "https://example.com/webservice/pluginfile.php/1/a.pdf?token=redacted&forcedownload=1",
+                }],
+            })
+        );
+        assert!(recording.matches("core_webservice_get_site_info", &[]));
+        assert!(!recording.matches("core_course_get_contents", &[]));
+        assert!(recording
+            .file_name()
+            .starts_with("core_webservice_get_site_info-"));
+    }
+
+    #[test]
+    fn test_file_name_ignores_param_order() {
+        let site_url = "https://moodle.example.edu/".parse().unwrap();
+        let recording = |params: &[(&str, &str)]| {
+            let params = params
+                .iter()
+                .map(|&(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+            Recording::new("core_course_get_contents", params, "[]", &site_url).unwrap()
+        };
+        let a = recording(&[("courseid", "42"), ("options[0][name]", "excludecontents")]);
+        let b = recording(&[
+            ("options[0][name]", "excludecontents"),
+            ("moodlewssettinglang", "en"),
+            ("courseid", "42"),
+        ]);
+        assert!(a.matches(&b.function, &b.params));
+        assert_eq!(a.file_name(), b.file_name());
+    }
+
+    #[test]
+    fn test_external_responses_redaction() {
+        let site_url = "https://moodle.example.edu/".parse().unwrap();
+        let data = json!([{ "author": "Jane Doe", "url": "https://moodle.example.edu/x" }]);
+        let response = json!({
+            "responses": [{ "error": false, "data": data.to_string(), "exception": null }],
+        });
+        let recording = Recording::new(
+            CallExternalFunctions::NAME,
+            Vec::new(),
+            &response.to_string(),
+            &site_url,
+        )
+        .unwrap();
+        let data = recording.response["responses"][0]["data"].as_str().unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(data).unwrap(),
+            json!([{ "author": "redacted", "url": "https://example.com/x" }])
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! Response from `core_course_get_categories`.
+
+use serde::Deserialize;
+use serde_with::serde_as;
+
+use crate::serde::{NumBool, StringAsHtml};
+
+#[serde_as]
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct Category {
+    pub id: u64,
+    #[serde_as(as = "StringAsHtml")]
+    pub name: String,
+    #[serde(rename = "idnumber")]
+    pub id_number: Option<String>,
+    /// The id of the parent category, `0` for top-level categories.
+    pub parent: u64,
+    /// The ids of all ancestors and the category itself, such as `/1/4`.
+    pub path: String,
+    pub depth: u64,
+    #[serde_as(as = "Option<NumBool>")]
+    #[serde(default)]
+    pub visible: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_category_deserialization() -> serde_json::Result<()> {
+        assert_eq!(
+            Category {
+                id: 4,
+                name: "Mathematics & Computer Science".to_string(),
+                id_number: Some(String::new()),
+                parent: 1,
+                path: "/1/4".to_string(),
+                depth: 2,
+                visible: Some(true),
+            },
+            serde_json::from_value(json!({
+                "id": 4,
+                "name": "Mathematics &amp; Computer Science",
+                "idnumber": "",
+                "description": "",
+                "descriptionformat": 1,
+                "parent": 1,
+                "sortorder": 20000,
+                "coursecount": 12,
+                "visible": 1,
+                "visibleold": 1,
+                "timemodified": 1029801600,
+                "depth": 2,
+                "path": "/1/4",
+                "theme": ""
+            }))?
+        );
+        Ok(())
+    }
+}
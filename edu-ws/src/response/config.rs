@@ -5,7 +5,7 @@ use serde_repr::Deserialize_repr;
 use serde_with::{serde_as, NoneAsEmptyString};
 use url::Url;
 
-use crate::serde::NumBool;
+use crate::{response::Warning, serde::NumBool};
 
 #[serde_as]
 #[derive(Deserialize, PartialEq, Debug)]
@@ -99,21 +99,22 @@ pub enum LoginType {
 #[serde_as]
 #[derive(Deserialize, PartialEq, Debug)]
 pub struct IdentityProvider {
-    name: String,
+    pub name: String,
     #[serde_as(as = "NoneAsEmptyString")]
     #[serde(rename = "iconurl")]
-    icon_url: Option<Url>,
-    url: Url,
+    pub icon_url: Option<Url>,
+    pub url: Url,
 }
 
-#[derive(Deserialize, PartialEq, Debug)]
-pub struct Warning {
-    item: Option<String>,
-    #[serde(rename = "itemid")]
-    item_id: Option<u64>,
-    #[serde(rename = "warningcode")]
-    warning_code: String,
-    message: String,
+impl IdentityProvider {
+    /// Returns the OAuth 2 issuer id to pass as `oauthsso` to the launch URL.
+    #[must_use]
+    pub fn id(&self) -> Option<u64> {
+        self.url
+            .query_pairs()
+            .find(|(key, _)| key == "id")
+            .and_then(|(_, value)| value.parse().ok())
+    }
 }
 
 #[cfg(test)]
@@ -278,6 +279,23 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_identity_provider_id() {
+        let identity_provider = |url: &str| IdentityProvider {
+            name: "name".to_string(),
+            icon_url: None,
+            url: url.parse().unwrap(),
+        };
+        assert_eq!(
+            Some(2),
+            identity_provider(
+                "https://example.com/auth/oauth2/login.php?id=2&wantsurl=%2F&sesskey=abc"
+            )
+            .id()
+        );
+        assert_eq!(None, identity_provider("https://example.com").id());
+    }
+
     #[test]
     fn test_warning_deserialization() -> serde_json::Result<()> {
         assert_eq!(
@@ -0,0 +1,60 @@
+//! Response from `tool_mobile_call_external_functions`.
+
+use serde::Deserialize;
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct ExternalResponses {
+    pub responses: Vec<ExternalResponse>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct ExternalResponse {
+    pub error: bool,
+    /// The JSON-encoded response, if successful.
+    pub data: Option<String>,
+    /// The JSON-encoded error, if unsuccessful.
+    pub exception: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_external_responses_deserialization() -> serde_json::Result<()> {
+        assert_eq!(
+            ExternalResponses {
+                responses: vec![
+                    ExternalResponse {
+                        error: false,
+                        data: Some("[]".to_string()),
+                        exception: None,
+                    },
+                    ExternalResponse {
+                        error: true,
+                        data: None,
+                        exception: Some(
+                            r#"{"errorcode":"invalidrecord","message":"Can't find data record in database table course."}"#
+                                .to_string()
+                        ),
+                    },
+                ]
+            },
+            serde_json::from_value(json!({
+                "responses": [
+                    {
+                        "error": false,
+                        "data": "[]"
+                    },
+                    {
+                        "error": true,
+                        "exception": "{\"errorcode\":\"invalidrecord\",\"message\":\"Can't find data record in database table course.\"}"
+                    }
+                ]
+            }))?
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+//! Responses from `mod_forum_get_forums_by_courses`,
+//! `mod_forum_get_forum_discussions` and `mod_forum_get_discussion_posts`.
+
+use serde::Deserialize;
+use serde_with::serde_as;
+use time::{serde::timestamp, OffsetDateTime};
+use url::Url;
+
+use crate::{response::SummaryFormat, serde::StringAsHtml};
+
+#[serde_as]
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct Forum {
+    pub id: u64,
+    pub course: u64,
+    #[serde(rename = "type")]
+    pub ty: Option<String>,
+    #[serde_as(as = "StringAsHtml")]
+    pub name: String,
+    pub intro: Option<String>,
+    #[serde(rename = "introformat")]
+    pub intro_format: Option<SummaryFormat>,
+    #[serde(rename = "cmid")]
+    pub course_module_id: Option<u64>,
+    #[serde(rename = "numdiscussions")]
+    pub discussion_count: Option<u64>,
+}
+
+#[serde_as]
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct Discussion {
+    /// The id of the first post.
+    pub id: u64,
+    /// The id of the discussion.
+    pub discussion: u64,
+    #[serde_as(as = "StringAsHtml")]
+    pub name: String,
+    #[serde(with = "timestamp::option", default)]
+    pub created: Option<OffsetDateTime>,
+    #[serde(with = "timestamp::option", default, rename = "timemodified")]
+    pub modified: Option<OffsetDateTime>,
+    #[serde(rename = "userfullname")]
+    pub user_full_name: Option<String>,
+    #[serde(rename = "numreplies")]
+    pub reply_count: Option<u64>,
+    pub pinned: Option<bool>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct Discussions {
+    pub discussions: Vec<Discussion>,
+}
+
+#[serde_as]
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct Post {
+    pub id: u64,
+    #[serde_as(as = "StringAsHtml")]
+    pub subject: String,
+    pub message: String,
+    #[serde(rename = "messageformat")]
+    pub message_format: SummaryFormat,
+    pub author: Author,
+    #[serde(rename = "discussionid")]
+    pub discussion_id: u64,
+    #[serde(rename = "parentid")]
+    pub parent_id: Option<u64>,
+    #[serde(with = "timestamp", rename = "timecreated")]
+    pub created: OffsetDateTime,
+    #[serde(rename = "isdeleted")]
+    pub deleted: Option<bool>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct Posts {
+    pub posts: Vec<Post>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct Author {
+    pub id: Option<u64>,
+    #[serde(rename = "fullname")]
+    pub full_name: Option<String>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct Attachment {
+    #[serde(rename = "filename")]
+    pub name: String,
+    #[serde(rename = "filesize")]
+    pub size: u64,
+    #[serde(alias = "fileurl")]
+    pub url: Url,
+    #[serde(with = "timestamp", rename = "timemodified")]
+    pub modified: OffsetDateTime,
+    #[serde(default, rename = "mimetype")]
+    pub media_type: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn test_forum_deserialization() -> serde_json::Result<()> {
+        assert_eq!(
+            Forum {
+                id: 1,
+                course: 2,
+                ty: Some("news".to_string()),
+                name: "Announcements & News".to_string(),
+                intro: Some("General news and announcements".to_string()),
+                intro_format: Some(SummaryFormat::Html),
+                course_module_id: Some(3),
+                discussion_count: Some(4),
+            },
+            serde_json::from_value(json!({
+                "id": 1,
+                "course": 2,
+                "type": "news",
+                "name": "Announcements &amp; News",
+                "intro": "General news and announcements",
+                "introformat": 1,
+                "introfiles": [],
+                "duedate": 0,
+                "cutoffdate": 0,
+                "cmid": 3,
+                "numdiscussions": 4,
+                "cancreatediscussions": false
+            }))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_discussions_deserialization() -> serde_json::Result<()> {
+        assert_eq!(
+            Discussions {
+                discussions: vec![Discussion {
+                    id: 5,
+                    discussion: 4,
+                    name: "Exam dates".to_string(),
+                    created: Some(datetime!(2002 - 08 - 20 0:00 UTC)),
+                    modified: Some(datetime!(2002 - 11 - 20 0:00 UTC)),
+                    user_full_name: Some("Jane Doe".to_string()),
+                    reply_count: Some(2),
+                    pinned: Some(false),
+                }]
+            },
+            serde_json::from_value(json!({
+                "discussions": [{
+                    "id": 5,
+                    "name": "Exam dates",
+                    "groupid": -1,
+                    "timemodified": 1037750400,
+                    "discussion": 4,
+                    "created": 1029801600,
+                    "subject": "Exam dates",
+                    "message": "<p>The exam takes place on Monday.</p>",
+                    "messageformat": 1,
+                    "userfullname": "Jane Doe",
+                    "numreplies": 2,
+                    "pinned": false
+                }],
+                "warnings": []
+            }))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_posts_deserialization() -> serde_json::Result<()> {
+        assert_eq!(
+            Posts {
+                posts: vec![Post {
+                    id: 5,
+                    subject: "Re: Exam dates".to_string(),
+                    message: "<p>Thanks!</p>".to_string(),
+                    message_format: SummaryFormat::Html,
+                    author: Author {
+                        id: Some(6),
+                        full_name: Some("John Doe".to_string()),
+                    },
+                    discussion_id: 4,
+                    parent_id: Some(4),
+                    created: datetime!(2002 - 08 - 20 0:00 UTC),
+                    deleted: Some(false),
+                    attachments: vec![Attachment {
+                        name: "notes.pdf".to_string(),
+                        size: 4096,
+                        url: "https://example.com/webservice/pluginfile.php/1/mod_forum/attachment/5/notes.pdf".parse().unwrap(),
+                        modified: datetime!(2002 - 08 - 20 0:00 UTC),
+                        media_type: Some("application/pdf".to_string()),
+                    }],
+                }]
+            },
+            serde_json::from_value(json!({
+                "posts": [{
+                    "id": 5,
+                    "subject": "Re: Exam dates",
+                    "message": "<p>Thanks!</p>",
+                    "messageformat": 1,
+                    "author": {
+                        "id": 6,
+                        "fullname": "John Doe"
+                    },
+                    "discussionid": 4,
+                    "hasparent": true,
+                    "parentid": 4,
+                    "timecreated": 1029801600,
+                    "isdeleted": false,
+                    "attachments": [{
+                        "filename": "notes.pdf",
+                        "filesize": 4096,
+                        "url": "https://example.com/webservice/pluginfile.php/1/mod_forum/attachment/5/notes.pdf",
+                        "timemodified": 1029801600,
+                        "mimetype": "application/pdf"
+                    }]
+                }],
+                "forumid": 1,
+                "courseid": 2,
+                "warnings": []
+            }))?
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+//! Response from `gradereport_user_get_grade_items`.
+
+use serde::Deserialize;
+use serde_with::serde_as;
+use time::{serde::timestamp, OffsetDateTime};
+
+use crate::{response::SummaryFormat, serde::StringAsHtml};
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct GradeItems {
+    #[serde(rename = "usergrades")]
+    pub user_grades: Vec<UserGrades>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct UserGrades {
+    #[serde(rename = "courseid")]
+    pub course_id: u64,
+    #[serde(rename = "userid")]
+    pub user_id: u64,
+    #[serde(rename = "userfullname")]
+    pub user_full_name: String,
+    #[serde(rename = "gradeitems")]
+    pub grade_items: Vec<GradeItem>,
+}
+
+#[serde_as]
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct GradeItem {
+    pub id: u64,
+    /// The name of the item, which is unset for the course total.
+    #[serde_as(as = "Option<StringAsHtml>")]
+    #[serde(default, rename = "itemname")]
+    pub name: Option<String>,
+    /// The type of the item, such as `mod`, `category`, `manual` or `course`.
+    #[serde(rename = "itemtype")]
+    pub ty: String,
+    #[serde(rename = "itemmodule")]
+    pub module: Option<String>,
+    #[serde(rename = "cmid")]
+    pub course_module_id: Option<u64>,
+    #[serde(rename = "gradeformatted")]
+    pub grade: Option<String>,
+    #[serde(rename = "rangeformatted")]
+    pub range: Option<String>,
+    #[serde(rename = "percentageformatted")]
+    pub percentage: Option<String>,
+    #[serde(rename = "lettergradeformatted")]
+    pub letter_grade: Option<String>,
+    #[serde(rename = "weightformatted")]
+    pub weight: Option<String>,
+    pub feedback: Option<String>,
+    #[serde(rename = "feedbackformat")]
+    pub feedback_format: Option<SummaryFormat>,
+    #[serde(with = "timestamp::option", default, rename = "gradedategraded")]
+    pub graded: Option<OffsetDateTime>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn test_grade_items_deserialization() -> serde_json::Result<()> {
+        assert_eq!(
+            GradeItems {
+                user_grades: vec![UserGrades {
+                    course_id: 2,
+                    user_id: 6,
+                    user_full_name: "John Doe".to_string(),
+                    grade_items: vec![
+                        GradeItem {
+                            id: 1,
+                            name: Some("Exercise 1 & 2".to_string()),
+                            ty: "mod".to_string(),
+                            module: Some("assign".to_string()),
+                            course_module_id: Some(3),
+                            grade: Some("9.00".to_string()),
+                            range: Some("0&ndash;10".to_string()),
+                            percentage: Some("90.00 %".to_string()),
+                            letter_grade: None,
+                            weight: Some("50.00 %".to_string()),
+                            feedback: Some("<p>Well done.</p>".to_string()),
+                            feedback_format: Some(SummaryFormat::Html),
+                            graded: Some(datetime!(2002 - 08 - 20 0:00 UTC)),
+                        },
+                        GradeItem {
+                            id: 2,
+                            name: None,
+                            ty: "course".to_string(),
+                            module: None,
+                            course_module_id: None,
+                            grade: Some("9.00".to_string()),
+                            range: Some("0&ndash;10".to_string()),
+                            percentage: None,
+                            letter_grade: None,
+                            weight: None,
+                            feedback: None,
+                            feedback_format: None,
+                            graded: None,
+                        },
+                    ],
+                }]
+            },
+            serde_json::from_value(json!({
+                "usergrades": [{
+                    "courseid": 2,
+                    "userid": 6,
+                    "userfullname": "John Doe",
+                    "maxdepth": 2,
+                    "gradeitems": [
+                        {
+                            "id": 1,
+                            "itemname": "Exercise 1 &amp; 2",
+                            "itemtype": "mod",
+                            "itemmodule": "assign",
+                            "iteminstance": 1,
+                            "itemnumber": 0,
+                            "cmid": 3,
+                            "gradeformatted": "9.00",
+                            "rangeformatted": "0&ndash;10",
+                            "percentageformatted": "90.00 %",
+                            "weightformatted": "50.00 %",
+                            "feedback": "<p>Well done.</p>",
+                            "feedbackformat": 1,
+                            "gradedategraded": 1029801600
+                        },
+                        {
+                            "id": 2,
+                            "itemname": null,
+                            "itemtype": "course",
+                            "itemmodule": null,
+                            "iteminstance": 2,
+                            "gradeformatted": "9.00",
+                            "rangeformatted": "0&ndash;10",
+                            "gradedategraded": null
+                        }
+                    ]
+                }],
+                "warnings": []
+            }))?
+        );
+        Ok(())
+    }
+}
@@ -1,10 +1,17 @@
 //! Responses to several web service requests.
 
+pub mod category;
 pub mod config;
 pub mod content;
 pub mod course;
+pub mod external;
+pub mod forum;
+pub mod grade;
 pub mod info;
 
+use std::fmt::{self, Display};
+
+use serde::Deserialize;
 use serde_repr::Deserialize_repr;
 
 #[derive(Deserialize_repr, Copy, Clone, Debug, Eq, Hash, PartialEq)]
@@ -15,3 +22,95 @@ pub enum SummaryFormat {
     Plain = 2,
     Markdown = 4,
 }
+
+/// A response that may contain warnings, for example about items that could
+/// not be returned.
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct WithWarnings<T> {
+    #[serde(flatten)]
+    pub data: T,
+    #[serde(default)]
+    pub warnings: Vec<Warning>,
+}
+
+impl<T> WithWarnings<T> {
+    /// Maps the data, keeping the warnings.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> WithWarnings<U> {
+        WithWarnings {
+            data: f(self.data),
+            warnings: self.warnings,
+        }
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+pub struct Warning {
+    pub item: Option<String>,
+    #[serde(rename = "itemid")]
+    pub item_id: Option<u64>,
+    #[serde(rename = "warningcode")]
+    pub warning_code: String,
+    pub message: String,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}", self.message, self.warning_code)?;
+        if let Some(item) = &self.item {
+            write!(f, ", {item}")?;
+            if let Some(item_id) = self.item_id {
+                write!(f, " {item_id}")?;
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::response::forum::Discussions;
+
+    #[test]
+    fn test_with_warnings_deserialization() -> serde_json::Result<()> {
+        let warning = Warning {
+            item: Some("forum".to_string()),
+            item_id: Some(1),
+            warning_code: "nopermissions".to_string(),
+            message: "You do not have permission.".to_string(),
+        };
+        assert_eq!(
+            WithWarnings {
+                data: Discussions {
+                    discussions: Vec::new()
+                },
+                warnings: vec![warning.clone()],
+            },
+            serde_json::from_value(json!({
+                "discussions": [],
+                "warnings": [{
+                    "item": "forum",
+                    "itemid": 1,
+                    "warningcode": "nopermissions",
+                    "message": "You do not have permission."
+                }]
+            }))?
+        );
+        assert_eq!(
+            warning.to_string(),
+            "You do not have permission. (nopermissions, forum 1)"
+        );
+        assert_eq!(
+            WithWarnings {
+                data: Discussions {
+                    discussions: Vec::new()
+                },
+                warnings: Vec::new(),
+            },
+            serde_json::from_value(json!({ "discussions": [] }))?
+        );
+        Ok(())
+    }
+}
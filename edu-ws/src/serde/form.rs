@@ -0,0 +1,551 @@
+//! A serializer for the form parameters of web service functions.
+//!
+//! Moodle parses parameters like PHP, so nested values are flattened into
+//! keys such as `courseids[0]` or `options[0][name]`. Booleans are serialized
+//! as `1` and `0`, like [`NumBool`].
+
+use std::fmt::Display;
+
+use serde::{
+    ser::{self, Impossible},
+    Serialize,
+};
+use serde_with::SerializeAs;
+use thiserror::Error;
+
+use crate::serde::NumBool;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("only structs and maps can be serialized as form parameters at the top level")]
+    TopLevel,
+    #[error("{0} cannot be serialized as form parameters")]
+    Unsupported(&'static str),
+    #[error("map keys must be strings, numbers or booleans")]
+    Key,
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+/// Serializes `value` into a list of form parameters.
+///
+/// `value` must be a struct or a map. `None` and unit values are omitted.
+pub fn to_form<T: Serialize + ?Sized>(value: &T) -> Result<Vec<(String, String)>, Error> {
+    let mut pairs = Vec::new();
+    value.serialize(Serializer {
+        key: None,
+        pairs: &mut pairs,
+    })?;
+    Ok(pairs)
+}
+
+/// Serializes a value at `key`, or the top-level value if `key` is unset.
+struct Serializer<'a> {
+    key: Option<String>,
+    pairs: &'a mut Vec<(String, String)>,
+}
+
+impl<'a> Serializer<'a> {
+    fn push(self, value: impl Display) -> Result<(), Error> {
+        let key = self.key.ok_or(Error::TopLevel)?;
+        self.pairs.push((key, value.to_string()));
+        Ok(())
+    }
+
+    fn nested(self) -> Nested<'a> {
+        Nested {
+            key: self.key,
+            pairs: self.pairs,
+            index: 0,
+            next_key: None,
+        }
+    }
+}
+
+/// Serializes the elements of a sequence, struct or map.
+struct Nested<'a> {
+    key: Option<String>,
+    pairs: &'a mut Vec<(String, String)>,
+    /// The index of the next sequence element.
+    index: usize,
+    /// The key of the next map value.
+    next_key: Option<String>,
+}
+
+impl Nested<'_> {
+    fn element<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        let key = match &self.key {
+            Some(prefix) => format!("{prefix}[{key}]"),
+            None => key.to_string(),
+        };
+        value.serialize(Serializer {
+            key: Some(key),
+            pairs: self.pairs,
+        })
+    }
+
+    fn next_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        if self.key.is_none() {
+            return Err(Error::TopLevel);
+        }
+        let index = self.index;
+        self.index += 1;
+        self.element(&index.to_string(), value)
+    }
+}
+
+impl<'a> ser::Serializer for Serializer<'a> {
+    type Error = Error;
+    type Ok = ();
+    type SerializeMap = Nested<'a>;
+    type SerializeSeq = Nested<'a>;
+    type SerializeStruct = Nested<'a>;
+    type SerializeStructVariant = Impossible<(), Error>;
+    type SerializeTuple = Nested<'a>;
+    type SerializeTupleStruct = Nested<'a>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        NumBool::serialize_as(&v, self)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.push(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.push(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.push(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.push(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.push(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.push(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.push(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.push(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.push(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.push(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.push(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.push(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), Error> {
+        Err(Error::Unsupported("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.push(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let mut nested = self.nested();
+        nested.element(variant, value)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Nested<'a>, Error> {
+        Ok(self.nested())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Nested<'a>, Error> {
+        Ok(self.nested())
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Nested<'a>, Error> {
+        Ok(self.nested())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::Unsupported("tuple variants"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Nested<'a>, Error> {
+        Ok(self.nested())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Nested<'a>, Error> {
+        Ok(self.nested())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::Unsupported("struct variants"))
+    }
+}
+
+impl ser::SerializeSeq for Nested<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.next_element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for Nested<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.next_element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for Nested<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.next_element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for Nested<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.next_key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.next_key.take().expect("serialize_key was not called");
+        self.element(&key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for Nested<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.element(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Serializes map keys into strings.
+struct KeySerializer;
+
+impl KeySerializer {
+    fn key(value: impl Display) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+}
+
+impl ser::Serializer for KeySerializer {
+    type Error = Error;
+    type Ok = String;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<String, Error> {
+        NumBool::serialize_as(&v, self)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String, Error> {
+        Self::key(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String, Error> {
+        Self::key(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String, Error> {
+        Self::key(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String, Error> {
+        Self::key(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String, Error> {
+        Self::key(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String, Error> {
+        Self::key(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String, Error> {
+        Self::key(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String, Error> {
+        Self::key(v)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, Error> {
+        Err(Error::Key)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, Error> {
+        Err(Error::Key)
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, Error> {
+        Self::key(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, Error> {
+        Self::key(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, Error> {
+        Err(Error::Key)
+    }
+
+    fn serialize_none(self) -> Result<String, Error> {
+        Err(Error::Key)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, Error> {
+        Err(Error::Key)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Error> {
+        Err(Error::Key)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, Error> {
+        Self::key(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Error> {
+        Err(Error::Key)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error::Key)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error::Key)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error::Key)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::Key)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::Key)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(Error::Key)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::Key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct ContentOption<'a> {
+        name: &'a str,
+        value: bool,
+    }
+
+    #[derive(Serialize)]
+    struct Params<'a> {
+        #[serde(rename = "courseids")]
+        course_ids: Vec<u64>,
+        options: Vec<ContentOption<'a>>,
+        lang: Option<&'a str>,
+        filter: BTreeMap<&'a str, u64>,
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_to_form() {
+        let params = Params {
+            course_ids: vec![2, 3],
+            options: vec![ContentOption {
+                name: "includestealthmodules",
+                value: true,
+            }],
+            lang: None,
+            filter: BTreeMap::from([("section", 1)]),
+        };
+        assert_eq!(
+            to_form(&params).unwrap(),
+            pairs(&[
+                ("courseids[0]", "2"),
+                ("courseids[1]", "3"),
+                ("options[0][name]", "includestealthmodules"),
+                ("options[0][value]", "1"),
+                ("filter[section]", "1"),
+            ])
+        );
+        assert_eq!(to_form(&()).unwrap(), pairs(&[]));
+        assert_eq!(to_form(&1), Err(Error::TopLevel));
+        assert_eq!(to_form(&[1, 2]), Err(Error::TopLevel));
+    }
+}
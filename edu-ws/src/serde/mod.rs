@@ -1,5 +1,7 @@
 //! Utilities for serde.
 
+pub mod form;
+
 use std::{borrow::Cow, convert::Infallible};
 
 use serde::{de::DeserializeOwned, Deserialize};
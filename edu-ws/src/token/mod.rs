@@ -1,6 +1,7 @@
 //! Tokens and SSO.
 
 pub mod login;
+pub mod qr;
 pub mod sso;
 
 use std::{str, string::ToString};
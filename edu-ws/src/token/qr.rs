@@ -0,0 +1,113 @@
+//! QR-code-based token creation.
+
+use std::str::FromStr;
+
+use thiserror::Error;
+use url::Url;
+
+use super::login;
+use crate::ajax;
+
+/// The content of a QR code for logging into the Moodle app.
+///
+/// Moodle encodes it as `moodlemobile://https://example.com?qrlogin=KEY&userid=ID`,
+/// where the URL scheme prefix may be missing or customized.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Payload {
+    pub site_url: Url,
+    pub qr_login_key: String,
+    pub user_id: u64,
+}
+
+#[derive(Error, PartialEq, Eq, Debug)]
+pub enum ParseError {
+    #[error("invalid site URL")]
+    InvalidUrl(#[from] url::ParseError),
+    #[error("QR code is not for logging in")]
+    MissingKey,
+    #[error("missing or invalid user id")]
+    InvalidUserId,
+}
+
+impl FromStr for Payload {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        // Strip the URL scheme of the app.
+        let s = s
+            .split_once("://")
+            .map(|(_, rest)| rest)
+            .filter(|rest| rest.contains("://"))
+            .unwrap_or(s);
+        let mut site_url = Url::parse(s)?;
+
+        let mut qr_login_key = None;
+        let mut user_id = None;
+        for (key, value) in site_url.query_pairs() {
+            match key.as_ref() {
+                "qrlogin" => qr_login_key = Some(value.into_owned()),
+                "userid" => user_id = Some(value.parse().or(Err(ParseError::InvalidUserId))?),
+                _ => {}
+            }
+        }
+        let qr_login_key = qr_login_key.ok_or(ParseError::MissingKey)?;
+        let user_id = user_id.ok_or(ParseError::InvalidUserId)?;
+
+        site_url.set_query(None);
+        site_url.set_fragment(None);
+        // Relative joins require the site root to end with a slash.
+        if !site_url.path().ends_with('/') {
+            let path = format!("{}/", site_url.path());
+            site_url.set_path(&path);
+        }
+
+        Ok(Self {
+            site_url,
+            qr_login_key,
+            user_id,
+        })
+    }
+}
+
+impl Payload {
+    /// Exchanges the QR login key for a token.
+    pub async fn exchange(
+        &self,
+        http_client: reqwest::Client,
+    ) -> Result<login::Response, ajax::Error> {
+        ajax::Client::new(http_client, &self.site_url)
+            .get_tokens_for_qr_login(&self.qr_login_key, self.user_id)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_parsing() {
+        let expected = Payload {
+            site_url: "https://example.com/moodle/".parse().unwrap(),
+            qr_login_key: "c0ffee".to_string(),
+            user_id: 42,
+        };
+        assert_eq!(
+            Ok(expected.clone()),
+            "moodlemobile://https://example.com/moodle?qrlogin=c0ffee&userid=42".parse()
+        );
+        assert_eq!(
+            Ok(expected),
+            "https://example.com/moodle/?userid=42&qrlogin=c0ffee\n".parse()
+        );
+        assert_eq!(
+            Err(ParseError::MissingKey),
+            "moodlemobile://https://example.com".parse::<Payload>()
+        );
+        assert_eq!(
+            Err(ParseError::InvalidUserId),
+            "https://example.com?qrlogin=c0ffee&userid=me".parse::<Payload>()
+        );
+    }
+}
@@ -1,30 +1,57 @@
 //! A client for web service requests.
 
-use std::{fmt, result};
+use std::{fmt, result, sync::Arc};
 
+pub use edu_ws_derive::WsFunction;
+use futures_util::{stream, StreamExt};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use serde_with::serde_as;
 use thiserror::Error;
 use tracing::{debug, error};
 use url::Url;
 
 use crate::{
-    response::{content::Section, course::Course, info::Info},
-    serde::NumBool,
+    capabilities::{SiteCapabilities, MOODLE_3_5_3, MOODLE_3_7},
+    function::{
+        CallExternalFunctions, ContentsOption, ExternalRequest, GetCategories, GetContents,
+        GetDiscussionPosts, GetForumDiscussions, GetForumsByCourses, GetGradeItems, GetSiteInfo,
+        GetUsersCourses,
+    },
+    record::Recorder,
+    response::{
+        category::Category,
+        content::Section,
+        course::Course,
+        forum::{Discussion, Forum, Post},
+        grade::UserGrades,
+        info::Info,
+        Warning, WithWarnings,
+    },
+    serde::form,
     token::Token,
 };
 
+/// A web service function, implemented by its parameters.
+///
+/// Derive it with `#[derive(WsFunction)]` and
+/// `#[ws_function(name = "...", response = ...)]`.
+pub trait WsFunction: Serialize + fmt::Debug + Sync {
+    /// The name of the function, such as `core_webservice_get_site_info`.
+    const NAME: &'static str;
+
+    /// The type of a successful response.
+    type Response: DeserializeOwned;
+}
+
+/// An error returned by a web service function, identified by its
+/// `errorcode`.
 #[derive(Error, Deserialize, Debug, PartialEq)]
-#[serde(tag = "errorcode")]
+#[serde(from = "RawError")]
 pub enum Error {
     #[error("access exception: {message}")]
-    #[serde(rename = "accessexception")]
     AccessException { message: String },
     #[error("course context not valid: {message}")]
-    #[serde(rename = "errorcoursecontextnotvalid")]
     CourseContextNotValid { message: String },
     #[error("invalid token: {message}")]
-    #[serde(rename = "invalidtoken")]
     InvalidToken { message: String },
     #[error("invalid param: {message}{}", {
         match debuginfo {
@@ -32,14 +59,111 @@ pub enum Error {
             None => String::new(),
         }
     })]
-    #[serde(rename = "errorinvalidparam")]
-    #[serde(alias = "invalidparameter")]
     InvalidParam {
+        /// `invalidparameter` or `errorinvalidparam`.
+        errorcode: String,
+        message: String,
+        debuginfo: Option<String>,
+    },
+    #[error("invalid record: {message}")]
+    InvalidRecord { message: String },
+    #[error("missing permissions: {message}")]
+    NoPermissions { message: String },
+    #[error("login required: {message}")]
+    RequireLogin { message: String },
+    #[error("service requires login: {message}")]
+    ServiceRequiresLogin { message: String },
+    #[error("service not available: {message}")]
+    ServiceNotAvailable { message: String },
+    #[error("site in maintenance: {message}")]
+    SiteMaintenance { message: String },
+    #[error("web services not enabled: {message}")]
+    WebServicesNotEnabled {
+        /// `webservicesnotenabled` or `enablewsdescription`.
+        errorcode: String,
+        message: String,
+    },
+    /// An error without a dedicated variant.
+    #[error("{errorcode}: {message}{}", {
+        match debuginfo {
+            Some(debuginfo) => format!(", debuginfo: {debuginfo}"),
+            None => String::new(),
+        }
+    })]
+    Other {
+        errorcode: String,
+        exception: Option<String>,
         message: String,
         debuginfo: Option<String>,
     },
 }
 
+#[derive(Deserialize)]
+struct RawError {
+    errorcode: String,
+    exception: Option<String>,
+    #[serde(default)]
+    message: String,
+    debuginfo: Option<String>,
+}
+
+impl From<RawError> for Error {
+    fn from(raw: RawError) -> Self {
+        let RawError {
+            errorcode,
+            exception,
+            message,
+            debuginfo,
+        } = raw;
+        match errorcode.as_str() {
+            "accessexception" => Self::AccessException { message },
+            "errorcoursecontextnotvalid" => Self::CourseContextNotValid { message },
+            "invalidtoken" => Self::InvalidToken { message },
+            "errorinvalidparam" | "invalidparameter" => Self::InvalidParam {
+                errorcode,
+                message,
+                debuginfo,
+            },
+            "invalidrecord" => Self::InvalidRecord { message },
+            "nopermissions" => Self::NoPermissions { message },
+            "requireloginerror" => Self::RequireLogin { message },
+            "servicerequireslogin" => Self::ServiceRequiresLogin { message },
+            "servicenotavailable" => Self::ServiceNotAvailable { message },
+            "sitemaintenance" => Self::SiteMaintenance { message },
+            "webservicesnotenabled" | "enablewsdescription" => {
+                Self::WebServicesNotEnabled { errorcode, message }
+            }
+            _ => Self::Other {
+                errorcode,
+                exception,
+                message,
+                debuginfo,
+            },
+        }
+    }
+}
+
+impl Error {
+    /// The `errorcode` of the error, as sent by the site.
+    #[must_use]
+    pub fn errorcode(&self) -> &str {
+        match self {
+            Self::AccessException { .. } => "accessexception",
+            Self::CourseContextNotValid { .. } => "errorcoursecontextnotvalid",
+            Self::InvalidToken { .. } => "invalidtoken",
+            Self::InvalidParam { errorcode, .. }
+            | Self::WebServicesNotEnabled { errorcode, .. } => errorcode,
+            Self::InvalidRecord { .. } => "invalidrecord",
+            Self::NoPermissions { .. } => "nopermissions",
+            Self::RequireLogin { .. } => "requireloginerror",
+            Self::ServiceRequiresLogin { .. } => "servicerequireslogin",
+            Self::ServiceNotAvailable { .. } => "servicenotavailable",
+            Self::SiteMaintenance { .. } => "sitemaintenance",
+            Self::Other { errorcode, .. } => errorcode,
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum RequestError {
     #[error(transparent)]
@@ -47,23 +171,61 @@ pub enum RequestError {
     #[error(transparent)]
     HttpError(#[from] reqwest::Error),
     #[error(transparent)]
+    Encode(#[from] form::Error),
+    #[error(transparent)]
+    EncodeArguments(#[from] serde_json::Error),
+    #[error(transparent)]
     Decode(#[from] serde_path_to_error::Error<serde_json::Error>),
+    #[error("the site does not provide {function}")]
+    Unsupported { function: &'static str },
 }
 
 impl RequestError {
     pub fn is_http(&self) -> bool {
         matches!(self, Self::HttpError(_))
     }
+
+    /// Whether the site does not provide the function.
+    ///
+    /// Moodle answers calls to functions missing from the service with an
+    /// access exception and calls to unknown functions with an invalid record
+    /// error. Other errors, such as an invalid token or site maintenance,
+    /// affect every request and are not covered.
+    pub fn is_unavailable(&self) -> bool {
+        matches!(
+            self,
+            Self::WsError(Error::AccessException { .. } | Error::InvalidRecord { .. })
+                | Self::Unsupported { .. }
+        )
+    }
+
+    pub fn is_invalid_token(&self) -> bool {
+        matches!(self, Self::WsError(Error::InvalidToken { .. }))
+    }
+
+    /// The web service error, if any.
+    #[must_use]
+    pub fn ws_error(&self) -> Option<&Error> {
+        match self {
+            Self::WsError(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 pub type Result<T> = result::Result<T, RequestError>;
 
+/// The number of concurrent requests when batching is not available.
+const FALLBACK_CONCURRENCY: usize = 8;
+
 #[derive(Debug)]
 pub struct Client {
     http_client: reqwest::Client,
     ws_url: Url,
     token: Token,
     lang: Option<String>,
+    capabilities: Option<Arc<SiteCapabilities>>,
+    recorder: Option<Arc<Recorder>>,
 }
 
 impl Client {
@@ -80,9 +242,51 @@ impl Client {
             ws_url,
             token,
             lang,
+            capabilities: None,
+            recorder: None,
         }
     }
 
+    /// Records all requests and responses with `recorder`.
+    #[must_use]
+    pub fn with_recorder(mut self, recorder: Arc<Recorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Sets the capabilities of the site, which are used to choose
+    /// parameters and to reject unavailable functions without a request.
+    #[must_use]
+    pub fn with_capabilities(mut self, capabilities: Arc<SiteCapabilities>) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
+    #[must_use]
+    pub fn capabilities(&self) -> Option<&SiteCapabilities> {
+        self.capabilities.as_deref()
+    }
+
+    /// Whether the site provides `function`.
+    ///
+    /// Returns `true` if the capabilities of the site are unknown.
+    #[must_use]
+    pub fn supports(&self, function: &str) -> bool {
+        self.capabilities
+            .as_ref()
+            .is_none_or(|capabilities| capabilities.supports(function))
+    }
+
+    /// Whether the site runs at least the Moodle `version`.
+    ///
+    /// Returns `true` if the version of the site is unknown.
+    fn is_at_least(&self, version: u64) -> bool {
+        self.capabilities
+            .as_ref()
+            .and_then(|capabilities| capabilities.is_at_least(version))
+            .unwrap_or(true)
+    }
+
     async fn call_web_service<T, P>(&self, function: &str, params: Option<&P>) -> Result<T>
     where
         T: DeserializeOwned,
@@ -116,6 +320,11 @@ impl Client {
         }
 
         debug!(function, params = tracing::field::debug(params));
+        let form = form::to_form(&Params {
+            filter: true,
+            params,
+            lang: self.lang.as_deref(),
+        })?;
         let response = self
             .http_client
             .post(self.ws_url.clone())
@@ -124,59 +333,81 @@ impl Client {
                 function,
                 rest_format: "json",
             })
-            .form(&Params {
-                filter: true,
-                params,
-                lang: self.lang.as_deref(),
-            })
+            .form(&form)
             .send()
             .await?
             .text()
             .await
             .unwrap();
         debug!(response);
+        if let Some(recorder) = &self.recorder {
+            let site_url = self.ws_url.join("../../").unwrap();
+            recorder.record(function, &form, &response, &site_url);
+        }
 
-        let de = &mut serde_json::Deserializer::from_str(&response);
-        let ok_err = match serde_path_to_error::deserialize(de) {
-            Ok(value) => return Ok(value),
-            Err(err) => err,
-        };
+        decode(&response)
+    }
 
-        let de = &mut serde_json::Deserializer::from_str(&response);
-        match serde_path_to_error::deserialize(de) {
-            Ok(value) => Err(RequestError::WsError(value)),
-            Err(err) => {
-                error!(%ok_err, "Could not deserialize response");
-                error!(%err, "Could not deserialize error");
-                Err(RequestError::Decode(ok_err))
-            }
+    /// Calls the web service function `F` with `params`.
+    ///
+    /// Fails with [`RequestError::Unsupported`] if the site is known not to
+    /// provide `F`.
+    pub async fn call<F: WsFunction>(&self, params: &F) -> Result<F::Response> {
+        if !self.supports(F::NAME) {
+            return Err(RequestError::Unsupported { function: F::NAME });
         }
+        self.call_web_service(F::NAME, Some(params)).await
+    }
+
+    /// Calls the web service function `F` once for each of `params` in a
+    /// single request via `tool_mobile_call_external_functions`.
+    ///
+    /// Returns the results in the order of `params`.
+    pub async fn call_batch<F: WsFunction>(
+        &self,
+        params: &[F],
+    ) -> Result<Vec<Result<F::Response>>> {
+        if !self.supports(F::NAME) {
+            return Err(RequestError::Unsupported { function: F::NAME });
+        }
+        let requests = params
+            .iter()
+            .map(|params| {
+                Ok(ExternalRequest {
+                    function: F::NAME,
+                    arguments: serde_json::to_string(params)?,
+                    filter: true,
+                    lang: self.lang.as_deref(),
+                })
+            })
+            .collect::<result::Result<Vec<_>, serde_json::Error>>()?;
+        let responses = self.call(&CallExternalFunctions { requests }).await?;
+        let results = responses
+            .responses
+            .into_iter()
+            .map(|response| {
+                if response.error {
+                    Err(decode_error(
+                        response.exception.as_deref().unwrap_or("null"),
+                    ))
+                } else {
+                    decode(response.data.as_deref().unwrap_or("null"))
+                }
+            })
+            .collect();
+        Ok(results)
     }
 
     pub async fn get_info(&self) -> Result<Info> {
-        self.call_web_service::<_, ()>("core_webservice_get_site_info", None)
-            .await
+        self.call(&GetSiteInfo).await
     }
 
     pub async fn get_courses(&self, user_id: u64, return_user_count: bool) -> Result<Vec<Course>> {
-        #[serde_as]
-        #[derive(Serialize, Debug)]
-        struct Params {
-            #[serde(rename = "userid")]
-            user_id: u64,
-            #[serde_as(as = "Option<NumBool>")]
-            #[serde(rename = "returnusercount")]
-            return_user_count: Option<bool>,
-        }
-
         let mut res = self
-            .call_web_service::<Vec<Course>, _>(
-                "core_enrol_get_users_courses",
-                Some(&Params {
-                    user_id,
-                    return_user_count: Some(return_user_count),
-                }),
-            )
+            .call(&GetUsersCourses {
+                user_id,
+                return_user_count: self.is_at_least(MOODLE_3_7).then_some(return_user_count),
+            })
             .await;
 
         // moodle has introduced `includestealthmodules` in version 3.7:
@@ -191,13 +422,10 @@ impl Client {
             if debuginfo.contains("returnusercount") {
                 debug!("retrying without returnusercount");
                 res = self
-                    .call_web_service::<Vec<Course>, _>(
-                        "core_enrol_get_users_courses",
-                        Some(&Params {
-                            user_id,
-                            return_user_count: None,
-                        }),
-                    )
+                    .call(&GetUsersCourses {
+                        user_id,
+                        return_user_count: None,
+                    })
                     .await;
             }
         }
@@ -205,28 +433,57 @@ impl Client {
         res
     }
 
-    pub async fn get_contents(&self, course_id: u64) -> Result<Vec<Section>> {
-        #[serde_as]
-        #[derive(Serialize, Debug)]
-        struct Params<'a> {
-            #[serde(rename = "courseid")]
-            course_id: u64,
-            #[serde(rename = "options[0][name]")]
-            include_stealth_modules_name: Option<&'a str>,
-            #[serde_as(as = "Option<NumBool>")]
-            #[serde(rename = "options[0][value]")]
-            include_stealth_modules_value: Option<bool>,
-        }
+    /// Returns all course categories visible to the user.
+    pub async fn get_categories(&self) -> Result<Vec<Category>> {
+        self.call(&GetCategories).await
+    }
+
+    pub async fn get_forums(&self, course_id: u64) -> Result<Vec<Forum>> {
+        self.call(&GetForumsByCourses {
+            course_ids: vec![course_id],
+        })
+        .await
+    }
+
+    /// Returns all discussions of a forum.
+    ///
+    /// Requires Moodle 3.7 or later.
+    pub async fn get_discussions(&self, forum_id: u64) -> Result<WithWarnings<Vec<Discussion>>> {
+        let response = self.call(&GetForumDiscussions { forum_id }).await?;
+        trace_warnings(GetForumDiscussions::NAME, &response.warnings);
+        Ok(response.map(|discussions| discussions.discussions))
+    }
+
+    /// Returns all posts of a discussion, oldest first.
+    ///
+    /// Requires Moodle 3.7 or later.
+    pub async fn get_posts(&self, discussion_id: u64) -> Result<WithWarnings<Vec<Post>>> {
+        let response = self
+            .call(&GetDiscussionPosts {
+                discussion_id,
+                sort_direction: "ASC",
+            })
+            .await?;
+        trace_warnings(GetDiscussionPosts::NAME, &response.warnings);
+        Ok(response.map(|posts| posts.posts))
+    }
+
+    pub async fn get_grade_items(
+        &self,
+        course_id: u64,
+        user_id: u64,
+    ) -> Result<WithWarnings<Vec<UserGrades>>> {
+        let response = self.call(&GetGradeItems { course_id, user_id }).await?;
+        trace_warnings(GetGradeItems::NAME, &response.warnings);
+        Ok(response.map(|grade_items| grade_items.user_grades))
+    }
 
+    pub async fn get_contents(&self, course_id: u64) -> Result<Vec<Section>> {
         let mut res = self
-            .call_web_service(
-                "core_course_get_contents",
-                Some(&Params {
-                    course_id,
-                    include_stealth_modules_name: Some("includestealthmodules"),
-                    include_stealth_modules_value: Some(true),
-                }),
-            )
+            .call(&GetContents {
+                course_id,
+                options: self.contents_options(),
+            })
             .await;
 
         // moodle has introduced `includestealthmodules` in version 3.5.3:
@@ -236,20 +493,100 @@ impl Client {
         if let Err(RequestError::WsError(Error::InvalidParam { message, .. })) = &res {
             if message.contains("includestealthmodules") {
                 res = self
-                    .call_web_service(
-                        "core_course_get_contents",
-                        Some(&Params {
-                            course_id,
-                            include_stealth_modules_name: None,
-                            include_stealth_modules_value: None,
-                        }),
-                    )
+                    .call(&GetContents {
+                        course_id,
+                        options: Vec::new(),
+                    })
                     .await;
             }
         }
 
         res
     }
+
+    /// Returns the sections of several courses in a single request.
+    ///
+    /// Falls back to a request per course if batching is not available.
+    pub async fn get_contents_batch(
+        &self,
+        course_ids: &[u64],
+    ) -> Result<Vec<Result<Vec<Section>>>> {
+        let params = course_ids
+            .iter()
+            .map(|&course_id| GetContents {
+                course_id,
+                options: self.contents_options(),
+            })
+            .collect::<Vec<_>>();
+        let mut results = match self.call_batch(&params).await {
+            Err(err) if err.is_unavailable() => {
+                debug!(%err, "batching not available, requesting courses one by one");
+                let results = stream::iter(course_ids.iter().copied())
+                    .map(|course_id| self.get_contents(course_id))
+                    .buffered(FALLBACK_CONCURRENCY)
+                    .collect()
+                    .await;
+                return Ok(results);
+            }
+            res => res?,
+        };
+
+        // See `get_contents`.
+        for (&course_id, res) in course_ids.iter().zip(&mut results) {
+            if let Err(RequestError::WsError(Error::InvalidParam { message, .. })) = &res {
+                if message.contains("includestealthmodules") {
+                    *res = self.get_contents(course_id).await;
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    fn contents_options(&self) -> Vec<ContentsOption> {
+        if self.is_at_least(MOODLE_3_5_3) {
+            vec![ContentsOption::IncludeStealthModules(true)]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+fn trace_warnings(function: &str, warnings: &[Warning]) {
+    for warning in warnings {
+        debug!(function, %warning, "web service warning");
+    }
+}
+
+/// Deserializes a response or the error it contains.
+pub(crate) fn decode<T: DeserializeOwned>(response: &str) -> Result<T> {
+    let de = &mut serde_json::Deserializer::from_str(response);
+    let ok_err = match serde_path_to_error::deserialize(de) {
+        Ok(value) => return Ok(value),
+        Err(err) => err,
+    };
+
+    let de = &mut serde_json::Deserializer::from_str(response);
+    match serde_path_to_error::deserialize(de) {
+        Ok(value) => Err(RequestError::WsError(value)),
+        Err(err) => {
+            error!(%ok_err, "Could not deserialize response");
+            error!(%err, "Could not deserialize error");
+            Err(RequestError::Decode(ok_err))
+        }
+    }
+}
+
+/// Deserializes an error.
+fn decode_error(exception: &str) -> RequestError {
+    let de = &mut serde_json::Deserializer::from_str(exception);
+    match serde_path_to_error::deserialize(de) {
+        Ok(err) => RequestError::WsError(err),
+        Err(err) => {
+            error!(%err, "Could not deserialize error");
+            RequestError::Decode(err)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -273,6 +610,7 @@ mod tests {
 
         assert_eq!(
             Error::InvalidParam {
+                errorcode: "errorinvalidparam".to_string(),
                 message: "The param \"includestealthmodules\" is invalid.".to_string(),
                 debuginfo: None,
             },
@@ -285,6 +623,7 @@ mod tests {
 
         assert_eq!(
             Error::InvalidParam {
+                errorcode: "invalidparameter".to_string(),
                 message: "Invalid parameter value detected".to_string(),
                 debuginfo: Some(
                     "Unexpected keys (returnusercount) detected in parameter array.".to_string()
@@ -298,6 +637,44 @@ mod tests {
             }))?
         );
 
+        let err = serde_json::from_value::<Error>(json!({
+            "errorcode": "enablewsdescription",
+            "exception": "moodle_exception",
+            "message": "Web services must be enabled in Advanced features.",
+        }))?;
+        assert!(matches!(err, Error::WebServicesNotEnabled { .. }));
+        assert_eq!(err.errorcode(), "enablewsdescription");
+
+        assert_eq!(
+            Error::SiteMaintenance {
+                message: "Site is in maintenance".to_string()
+            },
+            serde_json::from_value(json!({
+                "errorcode": "sitemaintenance",
+                "exception": "moodle_exception",
+                "message": "Site is in maintenance",
+            }))?
+        );
+
+        let err = serde_json::from_value::<Error>(json!({
+            "errorcode": "usernotfullysetup",
+            "exception": "moodle_exception",
+            "message": "User not fully set-up",
+            "debuginfo": "Edit your profile",
+        }))?;
+        assert_eq!(
+            err,
+            Error::Other {
+                errorcode: "usernotfullysetup".to_string(),
+                exception: Some("moodle_exception".to_string()),
+                message: "User not fully set-up".to_string(),
+                debuginfo: Some("Edit your profile".to_string()),
+            }
+        );
+        assert_eq!(err.errorcode(), "usernotfullysetup");
+
+        assert!(serde_json::from_value::<Error>(json!([])).is_err());
+
         Ok(())
     }
 }
@@ -0,0 +1,138 @@
+//! Checks the recorded responses in `tests/corpus` and recording and replaying
+//! against the mock server.
+//!
+//! To add responses of a site to the corpus, sync with `EDU_SYNC_RECORD` set
+//! to a new directory in `tests/corpus`, such as `tests/corpus/moodle-4.1`,
+//! and review the recordings for personal data before committing them.
+
+use std::{fs, path::Path, sync::Arc};
+
+use edu_ws::{
+    function::{
+        CallExternalFunctions, GetCategories, GetContents, GetDiscussionPosts, GetForumDiscussions,
+        GetForumsByCourses, GetGradeItems, GetSiteInfo, GetUsersCourses,
+    },
+    record::{Recorder, Recording},
+    response::external::ExternalResponses,
+    token::Token,
+    ws::{self, Client, RequestError, WsFunction},
+};
+use edu_ws_mock::{fixture, Fixture, MockServer};
+
+/// Decodes the response of `recording` as the response of its function.
+///
+/// Web service errors are decoded as well.
+fn check(recording: &Recording) -> ws::Result<()> {
+    fn decode<F: WsFunction>(recording: &Recording) -> ws::Result<()> {
+        recording.decode::<F::Response>().map(drop)
+    }
+
+    let result = match recording.function.as_str() {
+        GetSiteInfo::NAME => decode::<GetSiteInfo>(recording),
+        GetUsersCourses::NAME => decode::<GetUsersCourses>(recording),
+        GetCategories::NAME => decode::<GetCategories>(recording),
+        GetContents::NAME => decode::<GetContents>(recording),
+        GetForumsByCourses::NAME => decode::<GetForumsByCourses>(recording),
+        GetForumDiscussions::NAME => decode::<GetForumDiscussions>(recording),
+        GetDiscussionPosts::NAME => decode::<GetDiscussionPosts<'_>>(recording),
+        GetGradeItems::NAME => decode::<GetGradeItems>(recording),
+        CallExternalFunctions::NAME => {
+            let responses = recording.decode::<ExternalResponses>()?.responses;
+            for (index, response) in responses.into_iter().enumerate() {
+                let function = recording
+                    .param(&format!("requests[{index}][function]"))
+                    .unwrap();
+                let response = match (response.data, response.exception) {
+                    (Some(data), _) => data,
+                    (None, Some(exception)) => exception,
+                    (None, None) => continue,
+                };
+                check(&Recording {
+                    function: function.to_string(),
+                    params: Vec::new(),
+                    response: serde_json::from_str(&response)?,
+                })?;
+            }
+            Ok(())
+        }
+        function => panic!("no response type known for {function}"),
+    };
+    match result {
+        Err(RequestError::WsError(_)) => Ok(()),
+        result => result,
+    }
+}
+
+#[test]
+fn test_corpus() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let recordings = Recording::read_dir(&dir).unwrap();
+    assert!(!recordings.is_empty());
+    let failures = recordings
+        .iter()
+        .filter_map(|(path, recording)| {
+            check(recording)
+                .err()
+                .map(|err| format!("{}: {err}", path.display()))
+        })
+        .collect::<Vec<_>>();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[tokio::test]
+async fn test_record_replay() {
+    let fixture = Fixture::example();
+    let token = fixture.user.token.parse::<Token>().unwrap();
+    let server = MockServer::start(fixture.clone()).await.unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let recorder = Arc::new(Recorder::new(dir.path().to_path_buf()));
+    let client =
+        Client::new(reqwest::Client::new(), server.url(), token, None).with_recorder(recorder);
+
+    let info = client.get_info().await.unwrap();
+    let client = client.with_capabilities(Arc::new((&info).into()));
+    client.get_courses(info.user_id, false).await.unwrap();
+    let sections = client.get_contents(42).await.unwrap();
+    let batch = client.get_contents_batch(&[42, 7]).await.unwrap();
+
+    let recordings = Recording::read_dir(dir.path()).unwrap();
+    assert_eq!(recordings.len(), 4);
+    for (path, recording) in &recordings {
+        check(recording).unwrap();
+        let json = fs::read_to_string(path).unwrap();
+        for personal in [
+            fixture.user.username.as_str(),
+            &fixture.user.first_name,
+            &fixture.user.token,
+            server.url().host_str().unwrap(),
+        ] {
+            assert!(
+                !json.contains(personal),
+                "{} contains {personal}",
+                path.display()
+            );
+        }
+    }
+
+    // The replaying site has no courses of its own.
+    let replaying = Fixture {
+        courses: Vec::new(),
+        recordings: fixture::Recording::read_dir(dir.path()).unwrap(),
+        ..fixture
+    };
+    let replay_server = MockServer::start(replaying).await.unwrap();
+    let replay_client = Client::new(reqwest::Client::new(), replay_server.url(), token, None)
+        .with_capabilities(Arc::new((&info).into()));
+    let replayed_info = replay_client.get_info().await.unwrap();
+    assert_eq!(replayed_info.full_name, "redacted");
+    assert_eq!(replayed_info.site_url.as_str(), "https://example.com/");
+    let replayed_sections = replay_client.get_contents(42).await.unwrap();
+    assert_eq!(replayed_sections.len(), sections.len());
+    assert_eq!(replayed_sections[1].name, sections[1].name);
+    let replayed_batch = replay_client.get_contents_batch(&[42, 7]).await.unwrap();
+    assert_eq!(replayed_batch.len(), batch.len());
+    assert_eq!(
+        replayed_batch[1].as_ref().unwrap()[0].modules[0].name,
+        "Script"
+    );
+}
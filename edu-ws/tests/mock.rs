@@ -0,0 +1,143 @@
+//! Tests of the clients against the mock server.
+
+use edu_ws::{
+    ajax,
+    token::{login, Token},
+    ws::{self, Client, RequestError},
+};
+use edu_ws_mock::{fixture::WsError, Fixture, MockServer};
+
+async fn start() -> (MockServer, Client) {
+    let fixture = Fixture::example();
+    let token = fixture.user.token.parse::<Token>().unwrap();
+    let server = MockServer::start(fixture).await.unwrap();
+    let client = Client::new(reqwest::Client::new(), server.url(), token, None);
+    (server, client)
+}
+
+#[tokio::test]
+async fn test_login() {
+    let (server, _) = start().await;
+    let client = login::Client::new(reqwest::Client::new(), server.url());
+
+    let response = client.login("student", "secret").await.unwrap();
+    assert_eq!(response.token.to_string(), server.fixture().user.token);
+    assert!(response.private_token.is_some());
+
+    let err = client.login("student", "wrong").await.unwrap_err();
+    assert!(matches!(err, login::ReceiveError::LoginRequestError(_)));
+}
+
+#[tokio::test]
+async fn test_public_config() {
+    let (server, _) = start().await;
+    let client = ajax::Client::new(reqwest::Client::new(), server.url());
+
+    let config = client.get_config().await.unwrap();
+    assert_eq!(config.site_name, "Mock Moodle");
+    assert!(config.web_services);
+    assert!(!config.maintenance);
+}
+
+#[tokio::test]
+async fn test_get_contents() {
+    let (server, client) = start().await;
+
+    let info = client.get_info().await.unwrap();
+    assert_eq!(info.full_name, "Sam Student");
+    assert_eq!(&info.site_url, server.url());
+
+    let courses = client.get_courses(info.user_id, false).await.unwrap();
+    assert_eq!(
+        courses.iter().map(|course| course.id).collect::<Vec<_>>(),
+        [42, 7]
+    );
+
+    let sections = client.get_contents(42).await.unwrap();
+    let content = &sections[1].modules[0].contents.as_ref().unwrap()[0];
+    assert_eq!(content.name, "sheet 1.pdf");
+    assert_eq!(content.size, 16);
+
+    let err = client.get_contents(1).await.unwrap_err();
+    assert!(matches!(
+        err.ws_error(),
+        Some(ws::Error::InvalidRecord { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_get_contents_batch() {
+    let (server, client) = start().await;
+
+    let results = client.get_contents_batch(&[42, 1, 7]).await.unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().len(), 2);
+    assert!(results[1].is_err());
+    assert_eq!(results[2].as_ref().unwrap()[0].name, "General");
+    assert_eq!(server.calls(), ["tool_mobile_call_external_functions"]);
+}
+
+#[tokio::test]
+async fn test_get_contents_batch_fallback() {
+    let (server, client) = start().await;
+
+    server.update(|fixture| {
+        fixture.errors.insert(
+            "tool_mobile_call_external_functions".to_string(),
+            WsError::new("accessexception", "Access control exception"),
+        );
+    });
+    let results = client.get_contents_batch(&[42, 7]).await.unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].as_ref().unwrap().len(), 2);
+    assert_eq!(results[1].as_ref().unwrap()[0].name, "General");
+
+    server.update(|fixture| fixture.site.maintenance = true);
+    let err = client.get_contents_batch(&[42, 7]).await.unwrap_err();
+    assert!(matches!(
+        err.ws_error(),
+        Some(ws::Error::SiteMaintenance { .. })
+    ));
+    assert_eq!(
+        server
+            .calls()
+            .iter()
+            .filter(|call| *call == "core_course_get_contents")
+            .count(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn test_errors() {
+    let (server, client) = start().await;
+
+    server.update(|fixture| {
+        fixture.errors.insert(
+            "core_course_get_contents".to_string(),
+            WsError::new("requireloginerror", "Course or activity not accessible."),
+        );
+    });
+    let err = client.get_contents(42).await.unwrap_err();
+    assert!(matches!(
+        err.ws_error(),
+        Some(ws::Error::RequireLogin { .. })
+    ));
+
+    server.update(|fixture| fixture.site.maintenance = true);
+    let err = client.get_info().await.unwrap_err();
+    assert!(matches!(
+        err.ws_error(),
+        Some(ws::Error::SiteMaintenance { .. })
+    ));
+
+    let client = Client::new(
+        reqwest::Client::new(),
+        server.url(),
+        "ffffffffffffffffffffffffffffffff".parse().unwrap(),
+        None,
+    );
+    let err = client.get_info().await.unwrap_err();
+    assert!(err.is_invalid_token());
+    assert!(matches!(err, RequestError::WsError(_)));
+}